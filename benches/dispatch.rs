@@ -0,0 +1,103 @@
+//! 事件分发的基准测试，通过 `Bot::handler_event` 分发消息与通知事件。
+//!
+//! 运行：`cargo bench --bench dispatch --features bench`
+
+use kovi::DispatchBench;
+use serde_json::{json, Value};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+struct CountingAlloc;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const PLUGINS: usize = 16;
+const LISTENS: usize = 4;
+const EVENTS: usize = 20_000;
+
+fn msg_event() -> Value {
+    json!({
+        "time": 1700000000,
+        "self_id": 10001,
+        "post_type": "message",
+        "message_type": "private",
+        "sub_type": "friend",
+        "message_id": 1,
+        "user_id": 20002,
+        "message": [{ "type": "text", "data": { "text": "hi" } }],
+        "raw_message": "hi",
+        "font": 0,
+        "sender": { "user_id": 20002, "nickname": "kovi" }
+    })
+}
+
+fn notice_event() -> Value {
+    json!({
+        "time": 1700000000,
+        "self_id": 10001,
+        "post_type": "notice",
+        "notice_type": "friend_add",
+        "user_id": 20002
+    })
+}
+
+async fn measure(name: &str, bench: &DispatchBench, done: &AtomicUsize, event: fn() -> Value) {
+    // 事件的构建不计入
+    let events: Vec<Value> = (0..EVENTS).map(|_| event()).collect();
+    done.store(0, Ordering::Relaxed);
+
+    let allocs = ALLOCS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for event in events {
+        bench.dispatch(event).await;
+    }
+    while done.load(Ordering::Relaxed) < EVENTS * PLUGINS * LISTENS {
+        tokio::task::yield_now().await;
+    }
+    let elapsed = start.elapsed();
+    let allocs = ALLOCS.load(Ordering::Relaxed) - allocs;
+
+    println!(
+        "{name:<10} {:>8.0} ns/event {:>8.1} allocs/event",
+        elapsed.as_nanos() as f64 / EVENTS as f64,
+        allocs as f64 / EVENTS as f64,
+    );
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let done = Arc::new(AtomicUsize::new(0));
+
+    runtime.block_on(async {
+        let done_ = done.clone();
+        let bench = DispatchBench::new(PLUGINS, LISTENS, move || {
+            done_.fetch_add(1, Ordering::Relaxed);
+        });
+
+        // 预热
+        measure("warmup", &bench, &done, msg_event).await;
+
+        measure("message", &bench, &done, msg_event).await;
+        measure("notice", &bench, &done, notice_event).await;
+    });
+    runtime.shutdown_timeout(Duration::from_secs(1));
+}
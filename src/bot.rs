@@ -1,7 +1,10 @@
 use ahash::{HashMapExt as _, RandomState};
+use arc_swap::ArcSwap;
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::{Input, Select};
+use parking_lot::Mutex;
 use plugin_builder::Listen;
+use runtimebot::onebot_api::LoginInfo;
 use serde::{Deserialize, Serialize};
 use serde_json::{self, Value};
 use std::collections::{HashMap, HashSet};
@@ -11,12 +14,13 @@ use std::future::Future;
 use std::io::Write as _;
 use std::net::{Ipv4Addr, Ipv6Addr};
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicI64};
+use std::time::Duration;
 use std::{fs, net::IpAddr, sync::Arc};
-use tokio::sync::mpsc::{self};
 use tokio::sync::{oneshot, watch};
 use tokio::task::JoinHandle;
 
-use crate::error::{BotBuildError, BotError};
+use crate::error::{ApiError, BotBuildError, BotError};
 use crate::task::TASK_MANAGER;
 
 #[cfg(feature = "plugin-access-control")]
@@ -28,6 +32,8 @@ pub(crate) mod run;
 
 pub mod message;
 pub mod plugin_builder;
+pub mod queue;
+pub(crate) mod rate_limit;
 pub mod runtimebot;
 
 tokio::task_local! {
@@ -43,6 +49,11 @@ tokio::task_local! {
 pub struct KoviConf {
     pub config: Config,
     pub server: Server,
+    /// 更多的 OneBot 账号，每个账号使用独立的连接，不填写则只连接 `server`。
+    ///
+    /// 反向 WebSocket 与 HTTP 需要为每个账号监听不同的端口
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub servers: Vec<Server>,
 }
 
 impl AsRef<KoviConf> for KoviConf {
@@ -56,6 +67,33 @@ pub struct Config {
     pub main_admin: i64,
     pub admins: Vec<i64>,
     pub debug: bool,
+    /// 等待 api 返回值的默认超时时间，单位毫秒，0 为不超时
+    #[serde(default = "Config::default_api_timeout")]
+    pub api_timeout: u64,
+    /// 事件与 api 队列的配置，不填写则使用默认值
+    #[serde(default)]
+    pub queue: QueueConfig,
+    /// api 发送限速，不填写则不限速
+    #[serde(default)]
+    pub rate_limit: RateLimit,
+    /// api 失败时的重试策略，不填写则不重试
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// 从文件或内存构建的图片、语音、短视频的大小限制，单位字节，0 为不限制。
+    ///
+    /// 插件通过 `RuntimeBot::get_media_size_limit` 获取，传入 `Message` 的 `_with_limit` 方法
+    #[serde(default = "Config::default_media_size_limit")]
+    pub media_size_limit: u64,
+}
+
+impl Config {
+    fn default_api_timeout() -> u64 {
+        30_000
+    }
+
+    fn default_media_size_limit() -> u64 {
+        message::media::DEFAULT_SIZE_LIMIT
+    }
 }
 
 impl KoviConf {
@@ -65,8 +103,14 @@ impl KoviConf {
                 main_admin,
                 admins: admins.unwrap_or_default(),
                 debug,
+                api_timeout: Config::default_api_timeout(),
+                queue: QueueConfig::default(),
+                rate_limit: RateLimit::default(),
+                retry: RetryPolicy::default(),
+                media_size_limit: Config::default_media_size_limit(),
             },
             server,
+            servers: Vec::new(),
         }
     }
 }
@@ -87,6 +131,53 @@ pub struct Bot {
     pub information: BotInformation,
     pub(crate) plugins: HashMap<String, BotPlugin, RandomState>,
     pub(crate) run_abort: Vec<tokio::task::AbortHandle>,
+    /// 已连接的账号，键为账号的 self_id
+    pub(crate) accounts: HashMap<i64, Arc<BotAccount>, RandomState>,
+    /// 第一个连接成功的账号，插件默认使用此账号
+    pub(crate) default_account: Option<Arc<BotAccount>>,
+    /// 事件分发使用的监听快照，通过 `publish_listen` 更新
+    pub(crate) listen: Arc<ArcSwap<ListenSnapshot>>,
+}
+
+/// 一个 OneBot 账号的连接
+pub(crate) struct BotAccount {
+    pub(crate) host: Host,
+    pub(crate) port: u16,
+    pub(crate) api_tx: queue::Sender<ApiAndOneshot>,
+    /// 事件队列的状态
+    pub(crate) event_queue: queue::QueueMonitor,
+    /// 账号的 self_id，收到第一个事件前为 0
+    pub(crate) self_id: AtomicI64,
+    /// 事件连接是否在线，用于避免重复触发断开事件
+    pub(crate) online: AtomicBool,
+    /// 最后一次心跳的间隔，连接断开后为 `None`
+    pub(crate) heartbeat: watch::Sender<Option<Duration>>,
+    /// 正在获取登录号信息的 lifecycle 任务，完成后触发连接事件
+    pub(crate) connecting: Mutex<Option<JoinHandle<()>>>,
+    /// 与 `Bot::listen` 相同，分发事件时不需要锁住 `Bot`
+    pub(crate) listen: Arc<ArcSwap<ListenSnapshot>>,
+}
+
+impl BotAccount {
+    pub(crate) fn new(
+        host: Host,
+        port: u16,
+        api_tx: queue::Sender<ApiAndOneshot>,
+        event_queue: queue::QueueMonitor,
+        listen: Arc<ArcSwap<ListenSnapshot>>,
+    ) -> Self {
+        BotAccount {
+            host,
+            port,
+            api_tx,
+            event_queue,
+            listen,
+            self_id: AtomicI64::new(0),
+            online: AtomicBool::new(false),
+            heartbeat: watch::channel(None).0,
+            connecting: Mutex::new(None),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -107,6 +198,30 @@ pub(crate) struct BotPlugin {
     pub(crate) access_list: AccessList,
 }
 
+/// 事件分发使用的监听快照。
+///
+/// 插件的监听、访问控制或管理员变化时整体替换，分发事件时只需要原子地读取。
+#[derive(Default)]
+pub(crate) struct ListenSnapshot {
+    pub(crate) plugins: Vec<Arc<PluginListen>>,
+    /// 主管理员与副管理员
+    pub(crate) admins: Arc<HashSet<i64>>,
+}
+
+/// 单个插件的监听
+pub(crate) struct PluginListen {
+    pub(crate) name: Arc<String>,
+    pub(crate) listen: Listen,
+    pub(crate) enabled: watch::Receiver<bool>,
+
+    #[cfg(feature = "plugin-access-control")]
+    pub(crate) access_control: bool,
+    #[cfg(feature = "plugin-access-control")]
+    pub(crate) list_mode: AccessControlMode,
+    #[cfg(feature = "plugin-access-control")]
+    pub(crate) access_list: AccessList,
+}
+
 #[cfg(feature = "plugin-access-control")]
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct AccessList {
@@ -149,7 +264,21 @@ struct PluginStatus {
 pub struct BotInformation {
     pub main_admin: i64,
     pub deputy_admins: HashSet<i64>,
+    /// 等待 api 返回值的默认超时时间，`None` 为不超时
+    pub api_timeout: Option<Duration>,
+    /// 事件与 api 队列的配置
+    pub queue: QueueConfig,
+    /// api 发送限速
+    pub rate_limit: RateLimit,
+    /// api 失败时默认的重试策略
+    pub retry: Arc<RetryPolicy>,
+    /// 从文件或内存构建的媒体的大小限制，单位字节，0 为不限制
+    pub media_size_limit: u64,
     pub server: Server,
+    /// 更多的 OneBot 账号
+    pub servers: Vec<Server>,
+    /// 已登录账号的信息，键为 self_id，每次 lifecycle 事件时刷新
+    pub self_infos: HashMap<i64, LoginInfo, RandomState>,
 }
 /// server信息
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -158,6 +287,241 @@ pub struct Server {
     pub port: u16,
     pub access_token: String,
     pub secure: bool,
+    /// 连接方式，不填写则为正向 WebSocket
+    #[serde(default)]
+    pub mode: ConnectMode,
+    /// 接收 HTTP POST 事件上报的配置，仅在 `mode = "http"` 时使用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_post: Option<HttpPost>,
+    /// 断线重连配置，不填写则使用默认值
+    #[serde(default)]
+    pub reconnect: Reconnect,
+}
+
+/// 与 OneBot 服务端的连接方式
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConnectMode {
+    /// 正向 WebSocket，Kovi 连接 OneBot 服务端的 `/event` 与 `/api`
+    #[default]
+    #[serde(rename = "websocket")]
+    WebSocket,
+    /// 正向 WebSocket Universal，Kovi 连接 OneBot 服务端的 `/`，事件与 api 共用一个连接
+    #[serde(rename = "websocket_universal")]
+    WebSocketUniversal,
+    /// 反向 WebSocket，Kovi 监听 `host:port`，等待 OneBot 服务端连接。
+    ///
+    /// 支持 Universal 连接（如 `/onebot/v11/ws`），也支持分开的 `/event` 与 `/api` 连接
+    #[serde(rename = "reverse_websocket")]
+    ReverseWebSocket,
+    /// HTTP，Kovi 通过 HTTP POST 向 `host:port` 调用 api，并在 `http_post` 配置的地址接收事件上报
+    #[serde(rename = "http")]
+    Http,
+}
+
+/// HTTP 连接方式下，接收 OneBot 事件上报的配置
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct HttpPost {
+    /// Kovi 监听的地址
+    pub host: Host,
+    /// Kovi 监听的端口
+    pub port: u16,
+    /// 上报签名密钥，与 OneBot 服务端的 `secret` 一致。为空则不校验 `X-Signature`
+    #[serde(default)]
+    pub secret: String,
+    /// 等待插件快速操作的最长时间，单位毫秒。超时后以空响应回复 OneBot 服务端
+    #[serde(default = "HttpPost::default_quick_operation_timeout")]
+    pub quick_operation_timeout: u64,
+}
+
+impl HttpPost {
+    pub fn new(host: Host, port: u16, secret: String) -> Self {
+        HttpPost {
+            host,
+            port,
+            secret,
+            quick_operation_timeout: Self::default_quick_operation_timeout(),
+        }
+    }
+
+    fn default_quick_operation_timeout() -> u64 {
+        3000
+    }
+}
+
+/// 断线重连配置
+///
+/// 连接断开后，Kovi 会按照指数退避的方式重新连接，期间插件、定时任务与 api 队列保持运行。
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Reconnect {
+    /// 是否启用断线重连，关闭后连接断开会直接关闭 Bot
+    pub enable: bool,
+    /// 第一次重连前等待的时间，单位毫秒
+    pub initial_delay: u64,
+    /// 两次重连之间最长的等待时间，单位毫秒
+    pub max_delay: u64,
+    /// 每次重连失败后，等待时间乘以此倍数
+    pub multiplier: f64,
+    /// 随机抖动比例，取值 0.0 ~ 1.0，等待时间会在 `±jitter` 的比例内随机浮动
+    pub jitter: f64,
+    /// 最多重连次数，0 为无限重连。超过次数后 Bot 会关闭
+    pub max_attempts: u32,
+}
+
+impl Default for Reconnect {
+    fn default() -> Self {
+        Reconnect {
+            enable: true,
+            initial_delay: 1000,
+            max_delay: 60_000,
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_attempts: 0,
+        }
+    }
+}
+
+/// 每个账号的事件队列与 api 队列配置
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct QueueConfig {
+    /// 从 OneBot 服务端收到、等待分发的事件
+    pub event: QueueOptions,
+    /// 插件发送、等待发往 OneBot 服务端的 api
+    pub api: QueueOptions,
+}
+
+/// 队列配置
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct QueueOptions {
+    /// 队列容量，最小为 1
+    pub capacity: usize,
+    /// 队列满时的处理方式
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for QueueOptions {
+    fn default() -> Self {
+        QueueOptions {
+            capacity: 1024,
+            overflow: OverflowPolicy::Block,
+        }
+    }
+}
+
+/// api 发送限速配置
+///
+/// 按照 api 的类别分别限速，超过速率的 api 会在队列中等待，不会被丢弃。
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct RateLimit {
+    /// 是否启用限速
+    pub enable: bool,
+    /// `send_` 开头的 api，如 `send_msg`、`send_group_forward_msg`
+    pub send_msg: RateLimitRule,
+    /// `get_` 与 `can_` 开头的查询
+    pub query: RateLimitRule,
+    /// 其余的 api，如群管理
+    pub other: RateLimitRule,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit {
+            enable: false,
+            send_msg: RateLimitRule {
+                global: Some(Rate {
+                    per_second: 2.0,
+                    burst: 5,
+                }),
+                group: Some(Rate {
+                    per_second: 1.0,
+                    burst: 3,
+                }),
+                user: Some(Rate {
+                    per_second: 1.0,
+                    burst: 3,
+                }),
+                jitter: 0,
+            },
+            query: RateLimitRule::default(),
+            other: RateLimitRule::default(),
+        }
+    }
+}
+
+/// 一类 api 的限速，不填写的速率不限制
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct RateLimitRule {
+    /// 此账号所有此类 api 的速率
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub global: Option<Rate>,
+    /// 发往同一个群（参数中的 `group_id`）的速率
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<Rate>,
+    /// 发往同一个用户（参数中的 `user_id`）的速率
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<Rate>,
+    /// 每次发送前额外随机等待的最长时间，单位毫秒，0 为不等待
+    pub jitter: u64,
+}
+
+/// 令牌桶的速率
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+pub struct Rate {
+    /// 每秒恢复的次数，不大于 0 时不限制
+    pub per_second: f64,
+    /// 最多可以连续发送的次数
+    pub burst: u32,
+}
+
+/// api 失败时的重试策略
+///
+/// 只有 `get_` 与 `can_` 开头的查询被视为幂等的。其他 api（发送消息、群管理等）只会在确定没有发送出去时
+/// （如队列已满）重试，超时或返回失败时不会重试，避免重复执行。
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// 最多尝试的次数，包括第一次，1 为不重试
+    pub max_attempts: u32,
+    /// 第一次重试前等待的时间，单位毫秒，之后每次翻倍
+    pub backoff: u64,
+    /// 重试前最长等待的时间，单位毫秒
+    pub max_backoff: u64,
+    /// 返回这些 retcode 时重试
+    pub retcodes: Vec<i32>,
+    /// 等待返回值超时，或连接在返回前断开时是否重试
+    pub retry_timeout: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            backoff: 500,
+            max_backoff: 5000,
+            retcodes: vec![1200],
+            retry_timeout: true,
+        }
+    }
+}
+
+/// 队列满时的处理方式
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// 等待队列有空位。事件队列满时暂停读取连接，api 队列满时 api 按顺序排队等待，不会阻塞插件的线程。
+    /// 等待的 api 也达到容量时返回 `ApiError::QueueFull`
+    #[default]
+    Block,
+    /// 丢弃最早进入队列的元素
+    DropOldest,
+    /// 丢弃新的元素
+    DropNewest,
+    /// 拒绝新的元素，api 返回 `ApiError::QueueFull`
+    Error,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -183,6 +547,9 @@ impl Server {
             port,
             access_token,
             secure,
+            mode: ConnectMode::default(),
+            http_post: None,
+            reconnect: Reconnect::default(),
         }
     }
 }
@@ -200,11 +567,17 @@ pub struct ApiReturn {
     pub retcode: i32,
     pub data: Value,
     pub echo: String,
+    /// 失败时的错误信息
+    #[serde(default, alias = "msg", skip_serializing_if = "String::is_empty")]
+    pub message: String,
+    /// 失败时对错误信息的自然语言描述
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub wording: String,
 }
 
 pub(crate) type ApiAndOneshot = (
     SendApi,
-    Option<oneshot::Sender<Result<ApiReturn, ApiReturn>>>,
+    Option<oneshot::Sender<Result<ApiReturn, ApiError>>>,
 );
 
 impl std::fmt::Display for ApiReturn {
@@ -293,13 +666,58 @@ impl Bot {
             information: BotInformation {
                 main_admin: conf.config.main_admin,
                 deputy_admins: conf.config.admins.iter().cloned().collect(),
+                api_timeout: match conf.config.api_timeout {
+                    0 => None,
+                    v => Some(Duration::from_millis(v)),
+                },
+                queue: conf.config.queue.clone(),
+                rate_limit: conf.config.rate_limit.clone(),
+                retry: Arc::new(conf.config.retry.clone()),
+                media_size_limit: conf.config.media_size_limit,
                 server: conf.server.clone(),
+                servers: conf.servers.clone(),
+                self_infos: HashMap::<_, _, RandomState>::new(),
             },
             plugins: HashMap::<_, _, RandomState>::new(),
             run_abort: Vec::new(),
+            accounts: HashMap::<_, _, RandomState>::new(),
+            default_account: None,
+            listen: Arc::new(ArcSwap::from_pointee(ListenSnapshot::default())),
         }
     }
 
+    /// 根据当前的插件与管理员生成新的监听快照。
+    ///
+    /// 修改插件的监听、访问控制或管理员后都需要调用。
+    pub(crate) fn publish_listen(&self) {
+        let plugins = self
+            .plugins
+            .values()
+            .map(|plugin| {
+                Arc::new(PluginListen {
+                    name: Arc::new(plugin.name.clone()),
+                    listen: plugin.listen.clone(),
+                    enabled: plugin.enabled.subscribe(),
+
+                    #[cfg(feature = "plugin-access-control")]
+                    access_control: plugin.access_control,
+                    #[cfg(feature = "plugin-access-control")]
+                    list_mode: plugin.list_mode,
+                    #[cfg(feature = "plugin-access-control")]
+                    access_list: plugin.access_list.clone(),
+                })
+            })
+            .collect();
+
+        let mut admins = self.information.deputy_admins.clone();
+        admins.insert(self.information.main_admin);
+
+        self.listen.store(Arc::new(ListenSnapshot {
+            plugins,
+            admins: Arc::new(admins),
+        }));
+    }
+
     /// 挂载插件的启动函数。
     pub fn mount_main<T>(&mut self, name: T, version: T, main: Arc<KoviAsyncFn>)
     where
@@ -582,18 +1000,18 @@ impl Bot {
     }
 }
 
-/// 将配置文件写入磁盘
-fn config_file_write_and_return() -> Result<KoviConf, std::io::Error> {
-    enum HostType {
-        IPv4,
-        IPv6,
-        Domain,
-    }
+enum HostType {
+    IPv4,
+    IPv6,
+    Domain,
+}
 
+/// 在终端中询问 `target` 的地址
+fn input_host(target: &str) -> Host {
     let host_type: HostType = {
         let items = ["IPv4", "IPv6", "Domain"];
         let select = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt("What is the type of the host of the OneBot server?")
+            .with_prompt(format!("What is the type of the host of {target}?"))
             .items(&items)
             .default(0)
             .interact()
@@ -607,10 +1025,10 @@ fn config_file_write_and_return() -> Result<KoviConf, std::io::Error> {
         }
     };
 
-    let host = match host_type {
+    match host_type {
         HostType::IPv4 => {
             let ip = Input::with_theme(&ColorfulTheme::default())
-                .with_prompt("What is the IP of the OneBot server?")
+                .with_prompt(format!("What is the IP of {target}?"))
                 .default(Ipv4Addr::new(127, 0, 0, 1))
                 .interact_text()
                 .unwrap();
@@ -618,7 +1036,7 @@ fn config_file_write_and_return() -> Result<KoviConf, std::io::Error> {
         }
         HostType::IPv6 => {
             let ip = Input::with_theme(&ColorfulTheme::default())
-                .with_prompt("What is the IP of the OneBot server?")
+                .with_prompt(format!("What is the IP of {target}?"))
                 .default(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1))
                 .interact_text()
                 .unwrap();
@@ -626,27 +1044,84 @@ fn config_file_write_and_return() -> Result<KoviConf, std::io::Error> {
         }
         HostType::Domain => {
             let domain = Input::with_theme(&ColorfulTheme::default())
-                .with_prompt("What is the domain of the OneBot server?")
+                .with_prompt(format!("What is the domain of {target}?"))
                 .default("localhost".to_string())
                 .interact_text()
                 .unwrap();
             Host::Domain(domain)
         }
+    }
+}
+
+/// 将配置文件写入磁盘
+fn config_file_write_and_return() -> Result<KoviConf, std::io::Error> {
+    let mode: ConnectMode = {
+        let items = [
+            "WebSocket (Kovi connects to the OneBot server)",
+            "WebSocket Universal (Events and api share one connection)",
+            "Reverse WebSocket (The OneBot server connects to Kovi)",
+            "HTTP (Kovi calls the api over HTTP and receives events by HTTP POST)",
+        ];
+        let select = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("How does Kovi connect to the OneBot server?")
+            .items(&items)
+            .default(0)
+            .interact()
+            .unwrap();
+
+        match select {
+            0 => ConnectMode::WebSocket,
+            1 => ConnectMode::WebSocketUniversal,
+            2 => ConnectMode::ReverseWebSocket,
+            3 => ConnectMode::Http,
+            _ => panic!(), //不可能的事情
+        }
     };
 
+    // 反向 WebSocket 时，填写的是 Kovi 自己监听的地址
+    let target = match mode {
+        ConnectMode::WebSocket | ConnectMode::WebSocketUniversal | ConnectMode::Http => {
+            "the OneBot server"
+        }
+        ConnectMode::ReverseWebSocket => "Kovi's reverse WebSocket listener",
+    };
+
+    let host = input_host(target);
+
     let port: u16 = Input::with_theme(&ColorfulTheme::default())
-        .with_prompt("What is the port of the OneBot server?")
+        .with_prompt(format!("What is the port of {target}?"))
         .default(8081)
         .interact_text()
         .unwrap();
 
     let access_token: String = Input::with_theme(&ColorfulTheme::default())
-        .with_prompt("What is the access_token of the OneBot server? (Optional)")
+        .with_prompt(format!("What is the access_token of {target}? (Optional)"))
         .default("".to_string())
         .show_default(false)
         .interact_text()
         .unwrap();
 
+    // HTTP 时，还需要 Kovi 接收事件上报的地址
+    let http_post = match mode {
+        ConnectMode::Http => {
+            let target = "Kovi's HTTP POST listener";
+            let host = input_host(target);
+            let port: u16 = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("What is the port of {target}?"))
+                .default(8082)
+                .interact_text()
+                .unwrap();
+            let secret: String = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("What is the secret of {target}? (Optional)"))
+                .default("".to_string())
+                .show_default(false)
+                .interact_text()
+                .unwrap();
+            Some(HttpPost::new(host, port, secret))
+        }
+        _ => None,
+    };
+
     let main_admin: i64 = Input::with_theme(&ColorfulTheme::default())
         .with_prompt("What is the ID of the main administrator? (Not used yet)")
         .allow_empty(true)
@@ -691,12 +1166,11 @@ fn config_file_write_and_return() -> Result<KoviConf, std::io::Error> {
         };
     }
 
-    let config = KoviConf::new(
-        main_admin,
-        None,
-        Server::new(host, port, access_token, secure),
-        false,
-    );
+    let mut server = Server::new(host, port, access_token, secure);
+    server.mode = mode;
+    server.http_post = http_post;
+
+    let config = KoviConf::new(main_admin, None, server, false);
 
     let mut doc = toml_edit::DocumentMut::new();
     doc["config"] = toml_edit::table();
@@ -719,6 +1193,24 @@ fn config_file_write_and_return() -> Result<KoviConf, std::io::Error> {
     doc["server"]["port"] = toml_edit::value(config.server.port as i64);
     doc["server"]["access_token"] = toml_edit::value(config.server.access_token.clone());
     doc["server"]["secure"] = toml_edit::value(config.server.secure);
+    if config.server.mode != ConnectMode::default() {
+        doc["server"]["mode"] = match config.server.mode {
+            ConnectMode::WebSocket => toml_edit::value("websocket"),
+            ConnectMode::WebSocketUniversal => toml_edit::value("websocket_universal"),
+            ConnectMode::ReverseWebSocket => toml_edit::value("reverse_websocket"),
+            ConnectMode::Http => toml_edit::value("http"),
+        };
+    }
+    if let Some(http_post) = &config.server.http_post {
+        let mut table = toml_edit::table();
+        table["host"] = match &http_post.host {
+            Host::IpAddr(ip) => toml_edit::value(ip.to_string()),
+            Host::Domain(domain) => toml_edit::value(domain.clone()),
+        };
+        table["port"] = toml_edit::value(http_post.port as i64);
+        table["secret"] = toml_edit::value(http_post.secret.clone());
+        doc["server"]["http_post"] = table;
+    }
 
     let file = fs::File::create("kovi.conf.toml")?;
     let mut writer = std::io::BufWriter::new(file);
@@ -758,12 +1250,12 @@ fn build_bot() {
     let conf = KoviConf::new(
         123456,
         None,
-        Server {
-            host: Host::IpAddr("127.0.0.1".parse().unwrap()),
-            port: 8081,
-            access_token: "".to_string(),
-            secure: false,
-        },
+        Server::new(
+            Host::IpAddr("127.0.0.1".parse().unwrap()),
+            8081,
+            "".to_string(),
+            false,
+        ),
         false,
     );
     let _ = Bot::build(conf);
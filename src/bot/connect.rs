@@ -1,275 +1,489 @@
-use super::Server;
-use super::{handler::InternalEvent, ApiAndOneshot, ApiReturn, Bot, Host};
+use super::{handler::InternalEvent, ApiReturn, Bot, Host};
+use super::{queue, rate_limit::ApiReceiver, ConnectMode, Reconnect, Server};
+use crate::error::{ApiError, QueueError};
+use ::http::HeaderValue;
 use ahash::{HashMapExt as _, RandomState};
-use futures_util::stream::{SplitSink, SplitStream};
-use futures_util::{SinkExt, StreamExt};
-use http::HeaderValue;
-use log::{debug, error, warn};
+use futures_util::stream::SplitStream;
+use futures_util::{Sink, SinkExt, StreamExt};
+use log::{debug, error, info, warn};
 use parking_lot::Mutex;
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::Value;
 use std::error::Error;
 use std::fmt::Display;
 use std::sync::RwLock;
+use std::time::Duration;
 use std::{collections::HashMap, net::IpAddr, sync::Arc};
 use tokio::net::TcpStream;
-use tokio::sync::mpsc::Sender;
-use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::tungstenite::handshake::client::Request;
 use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::{connect_async, tungstenite::client::IntoClientRequest};
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
 
-type ApiTxMap = Arc<
-    Mutex<HashMap<String, tokio::sync::oneshot::Sender<Result<ApiReturn, ApiReturn>>, RandomState>>,
->;
+mod http;
+mod reverse_ws;
+
+type ApiOneshotSender = tokio::sync::oneshot::Sender<Result<ApiReturn, ApiError>>;
+
+type ApiTxMap = Arc<Mutex<HashMap<String, ApiOneshotSender, RandomState>>>;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// 清理已超时 api 的间隔
+const API_SWEEP_INTERVAL: Duration = Duration::from_secs(10);
 
 impl Bot {
+    /// 根据 `Server` 的连接方式连接 OneBot 服务端
+    pub(crate) async fn connect(
+        server: Server,
+        api_rx: ApiReceiver,
+        event_tx: queue::Sender<InternalEvent>,
+        bot: Arc<RwLock<Bot>>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match server.mode {
+            ConnectMode::WebSocket => Self::ws_connect(server, api_rx, event_tx, bot).await,
+            ConnectMode::ReverseWebSocket => {
+                Self::reverse_ws_connect(server, api_rx, event_tx, bot).await
+            }
+            ConnectMode::WebSocketUniversal => {
+                Self::ws_universal_connect(server, api_rx, event_tx, bot).await
+            }
+            ConnectMode::Http => Self::http_connect(server, api_rx, event_tx, bot).await,
+        }
+    }
+
     pub(crate) async fn ws_connect(
         server: Server,
-        api_rx: mpsc::Receiver<ApiAndOneshot>,
-        event_tx: mpsc::Sender<InternalEvent>,
+        api_rx: ApiReceiver,
+        event_tx: queue::Sender<InternalEvent>,
         bot: Arc<RwLock<Bot>>,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
-        #[allow(clippy::type_complexity)]
-        let (event_connected_tx, event_connected_rx): (
-            oneshot::Sender<Result<(), Box<dyn std::error::Error + Send + Sync>>>,
-            oneshot::Receiver<Result<(), Box<dyn std::error::Error + Send + Sync>>>,
-        ) = oneshot::channel();
-
-        #[allow(clippy::type_complexity)]
-        let (api_connected_tx, api_connected_rx): (
-            oneshot::Sender<Result<(), Box<dyn Error + Send + Sync>>>,
-            oneshot::Receiver<Result<(), Box<dyn Error + Send + Sync>>>,
-        ) = oneshot::channel();
+        // 第一次连接失败通常是配置错误，直接返回错误，不进行重连
+        let (event_res, api_res) = tokio::join!(
+            connect_async(ws_request(&server, "event")),
+            connect_async(ws_request(&server, "api"))
+        );
+        let (event_stream, _) = event_res?;
+        let (api_stream, _) = api_res?;
 
-        {
-            let mut bot_write = bot.write().unwrap();
-            bot_write.spawn(Self::ws_event_connect(
-                server.clone(),
-                event_tx.clone(),
-                event_connected_tx,
-                bot.clone(),
-            ));
-            bot_write.spawn(Self::ws_send_api(
-                server,
-                api_rx,
-                event_tx,
-                api_connected_tx,
-                bot.clone(),
-            ));
-        }
+        let mut bot_write = bot.write().unwrap();
+        bot_write.spawn(Self::ws_event_connect(
+            server.clone(),
+            event_stream,
+            event_tx.clone(),
+        ));
+        bot_write.spawn(Self::ws_send_api(server, api_stream, api_rx, event_tx));
 
-        let (res1, res2) = tokio::join!(event_connected_rx, api_connected_rx);
-        let (res1, res2) = (res1.unwrap(), res2.unwrap());
-        match (res1, res2) {
-            (Ok(_), Ok(_)) => Ok(()),
-            (Err(e), _) | (_, Err(e)) => Err(e),
-        }
+        Ok(())
     }
 
-    pub(crate) async fn ws_event_connect(
+    pub(crate) async fn ws_universal_connect(
         server: Server,
-        event_tx: mpsc::Sender<InternalEvent>,
-        connected_tx: oneshot::Sender<Result<(), Box<dyn Error + Send + Sync>>>,
+        api_rx: ApiReceiver,
+        event_tx: queue::Sender<InternalEvent>,
         bot: Arc<RwLock<Bot>>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // 第一次连接失败通常是配置错误，直接返回错误，不进行重连
+        let (ws_stream, _) = connect_async(ws_request(&server, "")).await?;
+
+        let mut bot_write = bot.write().unwrap();
+        bot_write.spawn(Self::ws_universal(server, ws_stream, api_rx, event_tx));
+
+        Ok(())
+    }
+
+    /// Universal 连接，事件与 api 共用一个连接，断开后自动重连
+    pub(crate) async fn ws_universal(
+        server: Server,
+        mut ws_stream: WsStream,
+        mut api_rx: ApiReceiver,
+        event_tx: queue::Sender<InternalEvent>,
     ) {
-        let (host, port, access_token, secure) =
-            (server.host, server.port, server.access_token, server.secure);
-
-        let protocol = if secure { "wss" } else { "ws" };
-        let mut request = match host {
-            Host::IpAddr(ip) => match ip {
-                IpAddr::V4(ip) => format!("{}://{}:{}/event", protocol, ip, port)
-                    .into_client_request()
-                    .unwrap(),
-                IpAddr::V6(ip) => format!("{}://[{}]:{}/event", protocol, ip, port)
-                    .into_client_request()
-                    .unwrap(),
-            },
-            Host::Domain(domain) => format!("{}://{}:{}/event", protocol, domain, port)
-                .into_client_request()
-                .unwrap(),
-        };
+        let api_tx_map: ApiTxMap = Arc::new(Mutex::new(HashMap::<_, _, RandomState>::new()));
 
-        //增加Authorization头
-        if !access_token.is_empty() {
-            request.headers_mut().insert(
-                "Authorization",
-                HeaderValue::from_str(&format!("Bearer {}", access_token)).unwrap(),
-            );
-        }
+        loop {
+            let (write, read) = ws_stream.split();
 
-        let (ws_stream, _) = match connect_async(request).await {
-            Ok(v) => v,
-            Err(e) => {
-                connected_tx.send(Err(e.into())).unwrap();
+            //读
+            let mut read_task = tokio::spawn(ws_universal_read(
+                read,
+                event_tx.clone(),
+                Arc::clone(&api_tx_map),
+            ));
+
+            //写
+            let queue_closed = tokio::select! {
+                end = ws_send_api_write(write, &mut api_rx, Arc::clone(&api_tx_map)) => {
+                    matches!(end, WriteEnd::QueueClosed)
+                }
+                _ = &mut read_task => false,
+            };
+
+            read_task.abort();
+            fail_pending_api(&api_tx_map);
+
+            if queue_closed {
                 return;
             }
-        };
+            notify_disconnected(&event_tx);
 
-        connected_tx.send(Ok(())).unwrap();
+            ws_stream = match reconnect(&server, "").await {
+                Some(v) => v,
+                None => {
+                    connection_failed_eprintln("Bot universal connection lost", event_tx).await;
+                    return;
+                }
+            };
+        }
+    }
 
-        let (_, read) = ws_stream.split();
+    /// 读取 event 连接，断开后自动重连
+    pub(crate) async fn ws_event_connect(
+        server: Server,
+        mut ws_stream: WsStream,
+        event_tx: queue::Sender<InternalEvent>,
+    ) {
+        loop {
+            while let Some(msg) = ws_stream.next().await {
+                let msg = match msg {
+                    Ok(msg) => msg,
+                    Err(e) => {
+                        warn!("Bot event connection error: {e}");
+                        break;
+                    }
+                };
+                if msg.is_close() {
+                    warn!("Bot event connection closed by server: {msg}");
+                    break;
+                }
+                if !msg.is_text() {
+                    continue;
+                }
 
-        let mut bot_write = bot.write().unwrap();
-        bot_write.spawn(read.for_each(move |msg| {
-            let event_tx = event_tx.clone();
-            async {
-                match msg {
-                    Ok(msg) => {
-                        if !msg.is_text() {
-                            return;
-                        }
-
-                        let text = msg.to_text().unwrap();
-                        if let Err(e) = event_tx
-                            .send(InternalEvent::OneBotEvent(text.to_string()))
-                            .await
-                        {
-                            debug!("通道关闭：{e}")
-                        }
+                let text = msg.to_text().unwrap();
+                let event: Value = match serde_json::from_str(text) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        warn!("Invalid event: {e}: {text}");
+                        continue;
                     }
-                    Err(e) => connection_failed_eprintln(e, event_tx).await,
+                };
+                if let Err(e @ QueueError::Closed(_)) =
+                    event_tx.send(InternalEvent::OneBotEvent(event)).await
+                {
+                    debug!("通道关闭：{e}");
+                    return;
                 }
             }
-        }));
+
+            notify_disconnected(&event_tx);
+            ws_stream = match reconnect(&server, "event").await {
+                Some(v) => v,
+                None => {
+                    connection_failed_eprintln("Bot event connection lost", event_tx).await;
+                    return;
+                }
+            };
+        }
     }
 
+    /// 发送 api 并读取返回值，断开后自动重连。
+    ///
+    /// 断线期间插件发送的 api 会留在 `api_rx` 队列中，重连后继续发送；
+    /// 已发送但未收到返回值的 api 会立即返回失败。
     pub(crate) async fn ws_send_api(
         server: Server,
-        api_rx: mpsc::Receiver<ApiAndOneshot>,
-        event_tx: mpsc::Sender<InternalEvent>,
-        connected_tx: oneshot::Sender<Result<(), Box<dyn std::error::Error + Send + Sync>>>,
-        bot: Arc<RwLock<Bot>>,
+        mut ws_stream: WsStream,
+        mut api_rx: ApiReceiver,
+        event_tx: queue::Sender<InternalEvent>,
     ) {
-        let (host, port, access_token, secure) =
-            (server.host, server.port, server.access_token, server.secure);
-
-        let protocol = if secure { "wss" } else { "ws" };
-        let mut request = match host {
-            Host::IpAddr(ip) => match ip {
-                IpAddr::V4(ip) => format!("{}://{}:{}/api", protocol, ip, port)
-                    .into_client_request()
-                    .unwrap(),
-                IpAddr::V6(ip) => format!("{}://[{}]:{}/api", protocol, ip, port)
-                    .into_client_request()
-                    .unwrap(),
-            },
-            Host::Domain(domain) => format!("{}://{}:{}/api", protocol, domain, port)
-                .into_client_request()
-                .unwrap(),
-        };
+        let api_tx_map: ApiTxMap = Arc::new(Mutex::new(HashMap::<_, _, RandomState>::new()));
 
-        //增加Authorization头
-        if !access_token.is_empty() {
-            request.headers_mut().insert(
-                "Authorization",
-                HeaderValue::from_str(&format!("Bearer {}", access_token)).unwrap(),
-            );
-        }
+        loop {
+            let (write, read) = ws_stream.split();
 
-        let (ws_stream, _) = match connect_async(request).await {
-            Ok(v) => v,
-            Err(e) => {
-                connected_tx.send(Err(e.into())).unwrap();
+            //读
+            let mut read_task = tokio::spawn(ws_send_api_read(read, Arc::clone(&api_tx_map)));
+
+            //写
+            let queue_closed = tokio::select! {
+                end = ws_send_api_write(write, &mut api_rx, Arc::clone(&api_tx_map)) => {
+                    matches!(end, WriteEnd::QueueClosed)
+                }
+                _ = &mut read_task => false,
+            };
+
+            read_task.abort();
+            fail_pending_api(&api_tx_map);
+
+            if queue_closed {
                 return;
             }
-        };
+            // 只有事件连接断开才通知插件，事件仍然在到达时不应该暂停插件
 
-        connected_tx.send(Ok(())).unwrap();
+            ws_stream = match reconnect(&server, "api").await {
+                Some(v) => v,
+                None => {
+                    connection_failed_eprintln("Bot api connection lost", event_tx).await;
+                    return;
+                }
+            };
+        }
+    }
+}
 
-        let (write, read) = ws_stream.split();
-        let api_tx_map: ApiTxMap = Arc::new(Mutex::new(HashMap::<_, _, RandomState>::new()));
+fn ws_request(server: &Server, path: &str) -> Request {
+    let protocol = if server.secure { "wss" } else { "ws" };
+    let port = server.port;
+    let url = match &server.host {
+        Host::IpAddr(IpAddr::V4(ip)) => format!("{}://{}:{}/{}", protocol, ip, port, path),
+        Host::IpAddr(IpAddr::V6(ip)) => format!("{}://[{}]:{}/{}", protocol, ip, port, path),
+        Host::Domain(domain) => format!("{}://{}:{}/{}", protocol, domain, port, path),
+    };
+    let mut request = url.into_client_request().unwrap();
+
+    //增加Authorization头
+    if !server.access_token.is_empty() {
+        request.headers_mut().insert(
+            "Authorization",
+            HeaderValue::from_str(&format!("Bearer {}", server.access_token)).unwrap(),
+        );
+    }
 
-        let mut bot_write = bot.write().unwrap();
+    request
+}
 
-        //读
-        bot_write.spawn(ws_send_api_read(
-            read,
-            event_tx.clone(),
-            Arc::clone(&api_tx_map),
-        ));
+/// 按照 `Reconnect` 配置不断重连，直到连接成功。如果未启用重连或超过重连次数，返回 None
+async fn reconnect(server: &Server, path: &str) -> Option<WsStream> {
+    let mut backoff = Backoff::new(&server.reconnect);
+
+    loop {
+        let delay = backoff.next_delay()?;
+        warn!(
+            "Bot {path} connection lost, reconnecting in {:.1}s (attempt {})",
+            delay.as_secs_f64(),
+            backoff.attempt
+        );
+        tokio::time::sleep(delay).await;
+
+        match connect_async(ws_request(server, path)).await {
+            Ok((ws_stream, _)) => {
+                info!("Bot {path} connection restored");
+                return Some(ws_stream);
+            }
+            Err(e) => warn!("Bot {path} reconnect failed: {e}"),
+        }
+    }
+}
 
-        //写
-        bot_write.spawn(ws_send_api_write(
-            write,
-            api_rx,
-            event_tx,
-            api_tx_map.clone(),
-        ));
+/// 指数退避计算器
+pub(crate) struct Backoff<'a> {
+    conf: &'a Reconnect,
+    attempt: u32,
+}
+
+impl<'a> Backoff<'a> {
+    pub(crate) fn new(conf: &'a Reconnect) -> Self {
+        Backoff { conf, attempt: 0 }
+    }
+
+    /// 下一次重连前需要等待的时间。如果未启用重连或超过重连次数，返回 None
+    pub(crate) fn next_delay(&mut self) -> Option<Duration> {
+        if !self.conf.enable
+            || (self.conf.max_attempts != 0 && self.attempt >= self.conf.max_attempts)
+        {
+            return None;
+        }
+
+        let base = self.conf.initial_delay as f64 * self.conf.multiplier.powi(self.attempt as i32);
+        let base = base.min(self.conf.max_delay as f64);
+
+        let jitter = self.conf.jitter.clamp(0.0, 1.0);
+        let factor = if jitter > 0.0 {
+            rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter)
+        } else {
+            1.0
+        };
+
+        self.attempt += 1;
+        // 加上抖动后仍然不超过 max_delay
+        let delay = (base * factor).min(self.conf.max_delay as f64);
+        Some(Duration::from_millis(delay as u64))
+    }
+}
+
+async fn ws_send_api_read(mut read: SplitStream<WsStream>, api_tx_map: ApiTxMap) {
+    while let Some(msg) = read.next().await {
+        match msg {
+            Ok(msg) => {
+                if msg.is_close() {
+                    warn!("Bot api connection closed by server: {msg}");
+                    return;
+                }
+                if !msg.is_text() {
+                    continue;
+                }
+                handle_api_return(msg.to_text().unwrap(), &api_tx_map);
+            }
+            Err(e) => {
+                warn!("Bot api connection error: {e}");
+                return;
+            }
+        }
     }
 }
 
-async fn ws_send_api_read(
-    read: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
-    event_tx: Sender<InternalEvent>,
+async fn ws_universal_read(
+    mut read: SplitStream<WsStream>,
+    event_tx: queue::Sender<InternalEvent>,
     api_tx_map: ApiTxMap,
 ) {
-    read.for_each(|msg| {
-        let event_tx = event_tx.clone();
-        async {
-            match msg {
-                Ok(msg) => handle_msg(msg, event_tx, api_tx_map.clone()).await,
-                Err(e) => connection_failed_eprintln(e, event_tx).await,
+    while let Some(msg) = read.next().await {
+        match msg {
+            Ok(msg) => {
+                if msg.is_close() {
+                    warn!("Bot universal connection closed by server: {msg}");
+                    return;
+                }
+                if !msg.is_text() {
+                    continue;
+                }
+                let text = msg.to_text().unwrap();
+                if !dispatch_universal_frame(text, &event_tx, &api_tx_map).await {
+                    return;
+                }
+            }
+            Err(e) => {
+                warn!("Bot universal connection error: {e}");
+                return;
             }
         }
-    })
-    .await;
+    }
+}
 
-    async fn handle_msg(
-        msg: tokio_tungstenite::tungstenite::Message,
-        event_tx: Sender<InternalEvent>,
-        api_tx_map: ApiTxMap,
-    ) {
-        if msg.is_close() {
-            connection_failed_eprintln(format!("{msg}\nBot api connection failed"), event_tx).await;
+fn handle_api_return(text: &str, api_tx_map: &ApiTxMap) {
+    debug!("{}", text);
+
+    let return_value: ApiReturn = match serde_json::from_str(text) {
+        Ok(v) => v,
+        Err(_) => {
+            warn!("Unknow api return： {text}");
             return;
         }
-        if !msg.is_text() {
+    };
+    dispatch_api_return(return_value, api_tx_map);
+}
+
+/// 把 api 返回值交给对应 echo 的等待者
+fn dispatch_api_return(return_value: ApiReturn, api_tx_map: &ApiTxMap) {
+    if return_value.status != "ok" {
+        warn!("Api return error: {return_value}")
+    }
+
+    if return_value.echo == "None" {
+        return;
+    }
+
+    let api_tx = match api_tx_map.lock().remove(&return_value.echo) {
+        Some(v) => v,
+        None => {
+            debug!("No one is waiting for api return: {}", return_value.echo);
             return;
         }
+    };
+    return_api(api_tx, return_value);
+}
 
-        let text = msg.to_text().unwrap();
-
-        debug!("{}", text);
+/// 将 api 返回值交给等待它的插件，status 不为 ok 时作为 Err 返回
+fn return_api(api_tx: ApiOneshotSender, return_value: ApiReturn) {
+    let r = if return_value.status.to_lowercase() == "ok" {
+        api_tx.send(Ok(return_value))
+    } else {
+        api_tx.send(Err(ApiError::from(return_value)))
+    };
 
-        let return_value: ApiReturn = match serde_json::from_str(text) {
-            Ok(v) => v,
-            Err(_) => {
-                warn!("Unknow api return： {text}");
-                return;
-            }
-        };
+    if r.is_err() {
+        log::debug!("Return Api to plugin failed, the receiver has been closed")
+    };
+}
 
-        if return_value.status != "ok" {
-            warn!("Api return error: {text}")
+/// Universal 连接中，事件与 api 返回值共用一个连接。有 `post_type` 的是事件，其余作为 api 返回值处理。
+///
+/// 如果事件队列已关闭，返回 false。队列已满时按照配置丢弃事件
+async fn dispatch_universal_frame(
+    text: &str,
+    event_tx: &queue::Sender<InternalEvent>,
+    api_tx_map: &ApiTxMap,
+) -> bool {
+    let frame = match serde_json::from_str::<Value>(text) {
+        Ok(v) => v,
+        Err(_) => {
+            warn!("Unknow frame： {text}");
+            return true;
         }
+    };
 
-        if return_value.echo == "None" {
-            return;
-        }
+    if frame.get("post_type").is_some() {
+        return !matches!(
+            event_tx.send(InternalEvent::OneBotEvent(frame)).await,
+            Err(QueueError::Closed(_))
+        );
+    }
 
-        let mut api_tx_map = api_tx_map.lock();
+    debug!("{}", text);
+    match ApiReturn::deserialize(frame) {
+        Ok(v) => dispatch_api_return(v, api_tx_map),
+        Err(_) => warn!("Unknow api return： {text}"),
+    }
+    true
+}
 
-        let api_tx = api_tx_map.remove(&return_value.echo).unwrap();
-        let r = if return_value.status.to_lowercase() == "ok" {
-            api_tx.send(Ok(return_value))
-        } else {
-            api_tx.send(Err(return_value))
-        };
+/// 连接断开时，已发送但未收到返回值的 api 全部返回失败
+fn fail_pending_api(api_tx_map: &ApiTxMap) {
+    let pending: Vec<_> = api_tx_map.lock().drain().collect();
+    if pending.is_empty() {
+        return;
+    }
 
-        if r.is_err() {
-            log::debug!("Return Api to plugin failed, the receiver has been closed")
-        };
+    warn!(
+        "Bot api connection lost, {} pending api call(s) failed",
+        pending.len()
+    );
+    for (_, api_tx) in pending {
+        let _ = api_tx.send(Err(ApiError::ConnectionClosed));
     }
 }
 
-async fn ws_send_api_write(
-    mut write: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
-    mut api_rx: mpsc::Receiver<ApiAndOneshot>,
-    event_tx: Sender<InternalEvent>,
+enum WriteEnd {
+    /// 连接断开
+    Disconnected,
+    /// 插件 api 队列关闭
+    QueueClosed,
+}
+
+async fn ws_send_api_write<W>(
+    mut write: W,
+    api_rx: &mut ApiReceiver,
     api_tx_map: ApiTxMap,
-) {
-    while let Some((api_msg, return_api_tx)) = api_rx.recv().await {
-        let event_tx = event_tx.clone();
+) -> WriteEnd
+where
+    W: Sink<Message> + Unpin,
+    W::Error: Display,
+{
+    let mut sweep = tokio::time::interval(API_SWEEP_INTERVAL);
+
+    loop {
+        let (api_msg, return_api_tx) = tokio::select! {
+            v = api_rx.recv() => match v {
+                Some(v) => v,
+                None => return WriteEnd::QueueClosed,
+            },
+            _ = sweep.tick() => {
+                sweep_expired_api(&api_tx_map);
+                continue;
+            }
+        };
         debug!("{}", api_msg);
 
         if &api_msg.echo != "None" {
@@ -283,25 +497,74 @@ async fn ws_send_api_write(
             };
         }
 
-        let msg = tokio_tungstenite::tungstenite::Message::text(api_msg.to_string());
+        let msg = Message::text(api_msg.to_string());
 
         if let Err(e) = write.send(msg).await {
-            connection_failed_eprintln(e, event_tx).await;
+            warn!("Bot api send failed: {e}");
+            return WriteEnd::Disconnected;
         }
     }
 }
 
-async fn connection_failed_eprintln<E>(e: E, event_tx: Sender<InternalEvent>)
+/// 清理已经没有人等待的 api，通常是插件等待超时
+fn sweep_expired_api(api_tx_map: &ApiTxMap) {
+    let mut api_tx_map = api_tx_map.lock();
+    let len = api_tx_map.len();
+    api_tx_map.retain(|_, api_tx| !api_tx.is_closed());
+    if api_tx_map.len() != len {
+        debug!("Swept {} expired api echo", len - api_tx_map.len());
+    }
+}
+
+/// 通知事件连接已断开，不受队列容量限制。只在事件连接与 Universal 连接断开时调用
+fn notify_disconnected(event_tx: &queue::Sender<InternalEvent>) {
+    let _ = event_tx.force_send(InternalEvent::KoviEvent(
+        crate::bot::handler::KoviEvent::Disconnected,
+    ));
+}
+
+async fn connection_failed_eprintln<E>(e: E, event_tx: queue::Sender<InternalEvent>)
 where
     E: Display,
 {
     log::error!("{e}\nBot connection failed, please check the configuration and restart.");
-    if let Err(e) = event_tx
-        .send(InternalEvent::KoviEvent(
-            crate::bot::handler::KoviEvent::Drop,
-        ))
-        .await
-    {
+    if let Err(e) = event_tx.force_send(InternalEvent::KoviEvent(
+        crate::bot::handler::KoviEvent::Drop,
+    )) {
         error!("通道关闭,{e}")
     };
 }
+
+#[test]
+fn backoff_delay() {
+    let conf = Reconnect {
+        enable: true,
+        initial_delay: 100,
+        max_delay: 1000,
+        multiplier: 2.0,
+        jitter: 0.0,
+        max_attempts: 6,
+    };
+    let mut backoff = Backoff::new(&conf);
+    let delays: Vec<u64> = std::iter::from_fn(|| backoff.next_delay())
+        .map(|d| d.as_millis() as u64)
+        .collect();
+    assert_eq!(delays, vec![100, 200, 400, 800, 1000, 1000]);
+
+    let conf = Reconnect {
+        jitter: 0.5,
+        max_attempts: 0,
+        ..conf
+    };
+    let mut backoff = Backoff::new(&conf);
+    for _ in 0..20 {
+        let d = backoff.next_delay().unwrap().as_millis() as u64;
+        assert!((50..=1000).contains(&d), "delay {d}ms out of range");
+    }
+
+    let conf = Reconnect {
+        enable: false,
+        ..conf
+    };
+    assert!(Backoff::new(&conf).next_delay().is_none());
+}
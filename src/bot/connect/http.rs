@@ -0,0 +1,366 @@
+use super::return_api;
+use crate::bot::rate_limit::ApiReceiver;
+use crate::bot::{handler::InternalEvent, queue, ApiReturn, Bot, Host, Server};
+use crate::error::{ApiError, QueueError};
+use hmac::{Hmac, Mac};
+use http::header::{AUTHORIZATION, CONTENT_TYPE};
+use http::{HeaderValue, Request, Response, StatusCode};
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper::body::{Body, Bytes};
+use hyper::service::service_fn;
+use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use log::{debug, info, warn};
+use serde_json::Value;
+use sha1::Sha1;
+use std::convert::Infallible;
+use std::error::Error;
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio::task::JoinSet;
+
+type HttpClient = Client<HttpConnector, Full<Bytes>>;
+
+/// HTTP POST 上报的请求体的最大长度，在校验签名前读取，超过时返回 413
+const MAX_POST_BODY: usize = 8 * 1024 * 1024;
+
+impl Bot {
+    /// HTTP 连接方式。api 通过 HTTP POST 发送到 `host:port/<action>`，事件通过 `http_post` 配置的地址接收。
+    pub(crate) async fn http_connect(
+        server: Server,
+        api_rx: ApiReceiver,
+        event_tx: queue::Sender<InternalEvent>,
+        bot: Arc<RwLock<Bot>>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let http_post = match &server.http_post {
+            Some(v) => v.clone(),
+            None => return Err("`mode = \"http\"` requires the `[server.http_post]` config".into()),
+        };
+
+        if server.secure {
+            warn!("HTTP connection does not support secure connection, `secure` is ignored");
+        }
+
+        let listener = match &http_post.host {
+            Host::IpAddr(ip) => TcpListener::bind((*ip, http_post.port)).await?,
+            Host::Domain(domain) => TcpListener::bind((domain.as_str(), http_post.port)).await?,
+        };
+
+        info!(
+            "Kovi is waiting for OneBot HTTP POST on {}",
+            listener.local_addr()?
+        );
+
+        let api_url = match &server.host {
+            Host::IpAddr(IpAddr::V6(ip)) => format!("http://[{}]:{}", ip, server.port),
+            host => format!("http://{}:{}", host, server.port),
+        };
+
+        let mut bot_write = bot.write().unwrap();
+        bot_write.spawn(http_post_listen(
+            listener,
+            http_post.secret,
+            Duration::from_millis(http_post.quick_operation_timeout),
+            event_tx,
+        ));
+        bot_write.spawn(http_send_api(api_url, server.access_token, api_rx));
+
+        Ok(())
+    }
+}
+
+/// 依次发送 api，保证发送顺序与插件调用顺序一致
+async fn http_send_api(api_url: String, access_token: String, mut api_rx: ApiReceiver) {
+    let client: HttpClient = Client::builder(TokioExecutor::new()).build_http();
+
+    while let Some((api_msg, return_api_tx)) = api_rx.recv().await {
+        debug!("{}", api_msg);
+
+        let return_value = match http_call_api(
+            &client,
+            &api_url,
+            &access_token,
+            &api_msg.action,
+            &api_msg.params,
+        )
+        .await
+        {
+            Ok(mut v) => {
+                v.echo = api_msg.echo;
+                v
+            }
+            Err(e) => {
+                warn!("Api {} request failed: {e}", api_msg.action);
+                if let Some(api_tx) = return_api_tx {
+                    let _ = api_tx.send(Err(e));
+                }
+                continue;
+            }
+        };
+
+        if return_value.status != "ok" {
+            warn!("Api return error: {return_value}")
+        }
+
+        if let Some(api_tx) = return_api_tx {
+            return_api(api_tx, return_value);
+        }
+    }
+}
+
+/// 请求没有到达 OneBot 服务端时返回 `ApiError::ConnectionClosed`，
+/// HTTP 状态码错误时返回 retcode 为状态码的 `ApiError::Failed`
+async fn http_call_api(
+    client: &HttpClient,
+    api_url: &str,
+    access_token: &str,
+    action: &str,
+    params: &serde_json::Value,
+) -> Result<ApiReturn, ApiError> {
+    let mut request =
+        Request::post(format!("{api_url}/{action}")).header(CONTENT_TYPE, "application/json");
+    if !access_token.is_empty() {
+        request = request.header(AUTHORIZATION, format!("Bearer {access_token}"));
+    }
+    let request = request
+        .body(Full::new(Bytes::from(params.to_string())))
+        .map_err(|e| failed(-1, e.to_string()))?;
+
+    let response = client.request(request).await.map_err(|e| {
+        debug!("{e}");
+        ApiError::ConnectionClosed
+    })?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(failed(status.as_u16() as i32, status.to_string()));
+    }
+
+    let body = response
+        .into_body()
+        .collect()
+        .await
+        .map_err(|e| {
+            debug!("{e}");
+            ApiError::ConnectionClosed
+        })?
+        .to_bytes();
+
+    debug!("{}", String::from_utf8_lossy(&body));
+
+    // HTTP 返回值中没有 echo 字段
+    let mut value: serde_json::Value =
+        serde_json::from_slice(&body).map_err(|e| failed(-1, e.to_string()))?;
+    if let Some(obj) = value.as_object_mut() {
+        obj.entry("echo").or_insert_with(|| "".into());
+    }
+    serde_json::from_value(value).map_err(|e| failed(-1, e.to_string()))
+}
+
+/// 没有从服务端得到正常的返回值时，构造一个失败的返回值
+fn failed(retcode: i32, message: String) -> ApiError {
+    ApiError::from(ApiReturn {
+        status: "failed".to_string(),
+        retcode,
+        data: serde_json::Value::Null,
+        echo: String::new(),
+        message,
+        wording: String::new(),
+    })
+}
+
+async fn http_post_listen(
+    listener: TcpListener,
+    secret: String,
+    quick_operation_timeout: Duration,
+    event_tx: queue::Sender<InternalEvent>,
+) {
+    let secret = Arc::new(secret);
+
+    // 监听任务被关闭时，所有连接也会一起关闭
+    let mut connections = JoinSet::new();
+
+    loop {
+        let (stream, addr) = match listener.accept().await {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("HTTP POST accept failed: {e}");
+                continue;
+            }
+        };
+
+        while connections.try_join_next().is_some() {}
+
+        let secret = secret.clone();
+        let event_tx = event_tx.clone();
+        connections.spawn(async move {
+            let service = service_fn(move |request| {
+                handle_post(
+                    request,
+                    secret.clone(),
+                    quick_operation_timeout,
+                    event_tx.clone(),
+                )
+            });
+            if let Err(e) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("HTTP POST connection from {addr} failed: {e}");
+            }
+        });
+    }
+}
+
+async fn handle_post<B>(
+    request: Request<B>,
+    secret: Arc<String>,
+    quick_operation_timeout: Duration,
+    event_tx: queue::Sender<InternalEvent>,
+) -> Result<Response<Full<Bytes>>, Infallible>
+where
+    B: Body<Data = Bytes>,
+    B::Error: Into<Box<dyn Error + Send + Sync>>,
+{
+    let signature = request
+        .headers()
+        .get("X-Signature")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+
+    let body = match Limited::new(request.into_body(), MAX_POST_BODY)
+        .collect()
+        .await
+    {
+        Ok(v) => v.to_bytes(),
+        Err(e) if e.is::<LengthLimitError>() => {
+            warn!("HTTP POST rejected: body is larger than {MAX_POST_BODY} bytes");
+            return Ok(empty_response(StatusCode::PAYLOAD_TOO_LARGE));
+        }
+        Err(e) => {
+            debug!("Read HTTP POST body failed: {e}");
+            return Ok(empty_response(StatusCode::BAD_REQUEST));
+        }
+    };
+
+    if !secret.is_empty() {
+        match signature {
+            None => return Ok(empty_response(StatusCode::UNAUTHORIZED)),
+            Some(signature) if !verify_signature(&secret, &body, &signature) => {
+                warn!("HTTP POST rejected: invalid X-Signature");
+                return Ok(empty_response(StatusCode::FORBIDDEN));
+            }
+            _ => {}
+        }
+    }
+
+    let event: Value = match serde_json::from_slice(&body) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Invalid HTTP POST event: {e}");
+            return Ok(empty_response(StatusCode::BAD_REQUEST));
+        }
+    };
+
+    let (quick_tx, quick_rx) = oneshot::channel();
+    match event_tx
+        .send(InternalEvent::OneBotHttpPost(event, quick_tx))
+        .await
+    {
+        Ok(_) => {}
+        // 队列已满，让 OneBot 服务端知道事件没有被处理
+        Err(QueueError::Full(_)) => {
+            return Ok(empty_response(StatusCode::SERVICE_UNAVAILABLE));
+        }
+        Err(e) => {
+            debug!("通道关闭：{e}");
+            return Ok(empty_response(StatusCode::NO_CONTENT));
+        }
+    }
+
+    // 等待插件的快速操作，所有处理函数结束或超时后以空响应回复
+    match tokio::time::timeout(quick_operation_timeout, quick_rx).await {
+        Ok(Ok(operation)) => {
+            debug!("Quick operation: {operation}");
+            let mut response = Response::new(Full::new(Bytes::from(operation.to_string())));
+            response
+                .headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            Ok(response)
+        }
+        _ => Ok(empty_response(StatusCode::NO_CONTENT)),
+    }
+}
+
+fn empty_response(status: StatusCode) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::new()));
+    *response.status_mut() = status;
+    response
+}
+
+/// 校验 `X-Signature: sha1=<hex>`，签名为使用 secret 对请求体计算的 HMAC-SHA1
+fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let signature = match signature.strip_prefix("sha1=") {
+        Some(v) => v,
+        None => return false,
+    };
+    let expected = match decode_hex(signature) {
+        Some(v) => v,
+        None => return false,
+    };
+
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[test]
+fn http_post_signature() {
+    // echo -n '{"post_type":"message"}' | openssl dgst -sha1 -hmac "secret"
+    let body = br#"{"post_type":"message"}"#;
+    let hex = "337a43569f539f97888127f17fefc625794e5e06";
+
+    assert!(verify_signature("secret", body, &format!("sha1={hex}")));
+    assert!(!verify_signature("other", body, &format!("sha1={hex}")));
+    assert!(!verify_signature("secret", body, hex));
+    assert!(!verify_signature("secret", body, "sha1=zz"));
+}
+
+#[tokio::test]
+async fn http_post_body_limit() {
+    let (event_tx, _event_rx) = queue::channel("event", &Default::default());
+    let request = |body: Vec<u8>| Request::new(Full::new(Bytes::from(body)));
+
+    let response = handle_post(
+        request(vec![b' '; MAX_POST_BODY + 1]),
+        Arc::new(String::from("secret")),
+        Duration::ZERO,
+        event_tx.clone(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let response = handle_post(
+        request(b"{}".to_vec()),
+        Arc::new(String::new()),
+        Duration::ZERO,
+        event_tx,
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+}
@@ -0,0 +1,308 @@
+use super::{
+    dispatch_universal_frame, fail_pending_api, handle_api_return, notify_disconnected,
+    ws_send_api_write, ApiTxMap,
+};
+use crate::bot::rate_limit::ApiReceiver;
+use crate::bot::{handler::InternalEvent, queue, Bot, Host, Server};
+use crate::error::QueueError;
+use ahash::{HashMapExt as _, RandomState};
+use futures_util::stream::SplitStream;
+use futures_util::StreamExt;
+use http::StatusCode;
+use log::{debug, info, warn};
+use parking_lot::Mutex;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinSet;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::{accept_hdr_async, WebSocketStream};
+
+/// 反向 WebSocket 连接的角色，对应 OneBot 实现发送的 `X-Client-Role` 头
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ClientRole {
+    Event,
+    Api,
+    Universal,
+}
+
+impl Display for ClientRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientRole::Event => write!(f, "Event"),
+            ClientRole::Api => write!(f, "API"),
+            ClientRole::Universal => write!(f, "Universal"),
+        }
+    }
+}
+
+impl ClientRole {
+    /// 优先使用 `X-Client-Role` 头判断，没有时根据路径判断
+    fn from_request(request: &Request) -> ClientRole {
+        if let Some(role) = request
+            .headers()
+            .get("X-Client-Role")
+            .and_then(|v| v.to_str().ok())
+        {
+            match role.to_lowercase().as_str() {
+                "event" => return ClientRole::Event,
+                "api" => return ClientRole::Api,
+                "universal" => return ClientRole::Universal,
+                _ => {}
+            }
+        }
+
+        let path = request.uri().path().trim_end_matches('/');
+        if path.ends_with("/event") {
+            ClientRole::Event
+        } else if path.ends_with("/api") {
+            ClientRole::Api
+        } else {
+            ClientRole::Universal
+        }
+    }
+
+    fn has_api(&self) -> bool {
+        matches!(self, ClientRole::Api | ClientRole::Universal)
+    }
+}
+
+impl Bot {
+    /// 反向 WebSocket，Kovi 监听 `host:port`，等待 OneBot 实现连接。
+    ///
+    /// 支持 Universal 连接（如 `/onebot/v11/ws`），也支持分开的 `/event` 与 `/api` 连接。
+    pub(crate) async fn reverse_ws_connect(
+        server: Server,
+        api_rx: ApiReceiver,
+        event_tx: queue::Sender<InternalEvent>,
+        bot: Arc<RwLock<Bot>>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if server.secure {
+            warn!("Reverse WebSocket does not support secure connection, `secure` is ignored");
+        }
+
+        let listener = match &server.host {
+            Host::IpAddr(ip) => TcpListener::bind((*ip, server.port)).await?,
+            Host::Domain(domain) => TcpListener::bind((domain.as_str(), server.port)).await?,
+        };
+
+        info!(
+            "Kovi is waiting for OneBot reverse WebSocket connection on {}",
+            listener.local_addr()?
+        );
+
+        let mut bot_write = bot.write().unwrap();
+        bot_write.spawn(reverse_ws_listen(
+            listener,
+            server.access_token,
+            api_rx,
+            event_tx,
+        ));
+
+        Ok(())
+    }
+}
+
+async fn reverse_ws_listen(
+    listener: TcpListener,
+    access_token: String,
+    api_rx: ApiReceiver,
+    event_tx: queue::Sender<InternalEvent>,
+) {
+    // 同一时间只有一个连接负责发送 api，其他 api 连接会等待它断开
+    let api_rx = Arc::new(tokio::sync::Mutex::new(api_rx));
+    let api_tx_map: ApiTxMap = Arc::new(Mutex::new(HashMap::<_, _, RandomState>::new()));
+    let access_token = Arc::new(access_token);
+
+    // 监听任务被关闭时，所有连接也会一起关闭
+    let mut connections = JoinSet::new();
+
+    loop {
+        let (stream, addr) = match listener.accept().await {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Reverse WebSocket accept failed: {e}");
+                continue;
+            }
+        };
+
+        while connections.try_join_next().is_some() {}
+
+        connections.spawn(handle_connection(
+            stream,
+            addr,
+            access_token.clone(),
+            api_rx.clone(),
+            event_tx.clone(),
+            api_tx_map.clone(),
+        ));
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    addr: SocketAddr,
+    access_token: Arc<String>,
+    api_rx: Arc<tokio::sync::Mutex<ApiReceiver>>,
+    event_tx: queue::Sender<InternalEvent>,
+    api_tx_map: ApiTxMap,
+) {
+    let mut role = ClientRole::Universal;
+
+    // ErrorResponse 的大小由 tungstenite 决定
+    #[allow(clippy::result_large_err)]
+    let callback = |request: &Request, response: Response| {
+        if !is_authorized(request, &access_token) {
+            warn!("Reverse WebSocket connection from {addr} rejected: invalid access_token");
+            let mut error = ErrorResponse::new(Some("Unauthorized".to_string()));
+            *error.status_mut() = StatusCode::UNAUTHORIZED;
+            return Err(error);
+        }
+        role = ClientRole::from_request(request);
+        Ok(response)
+    };
+
+    let ws_stream = match accept_hdr_async(stream, callback).await {
+        Ok(v) => v,
+        Err(e) => {
+            debug!("Reverse WebSocket handshake with {addr} failed: {e}");
+            return;
+        }
+    };
+
+    info!("OneBot connected from {addr} ({role})");
+
+    let (write, read) = ws_stream.split();
+
+    let mut read_task = tokio::spawn(reverse_ws_read(
+        read,
+        role,
+        event_tx.clone(),
+        Arc::clone(&api_tx_map),
+    ));
+
+    if role.has_api() {
+        tokio::select! {
+            _ = async {
+                let mut api_rx = api_rx.lock().await;
+                ws_send_api_write(write, &mut api_rx, Arc::clone(&api_tx_map)).await
+            } => {}
+            _ = &mut read_task => {}
+        }
+        read_task.abort();
+        fail_pending_api(&api_tx_map);
+    } else {
+        let _ = read_task.await;
+    }
+
+    warn!("OneBot {role} connection from {addr} closed");
+    if role != ClientRole::Api {
+        notify_disconnected(&event_tx);
+    }
+}
+
+async fn reverse_ws_read(
+    mut read: SplitStream<WebSocketStream<TcpStream>>,
+    role: ClientRole,
+    event_tx: queue::Sender<InternalEvent>,
+    api_tx_map: ApiTxMap,
+) {
+    while let Some(msg) = read.next().await {
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(e) => {
+                warn!("Reverse WebSocket connection error: {e}");
+                return;
+            }
+        };
+        if msg.is_close() {
+            return;
+        }
+        if !msg.is_text() {
+            continue;
+        }
+
+        let text = msg.to_text().unwrap();
+        match role {
+            ClientRole::Event => {
+                let event: Value = match serde_json::from_str(text) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        warn!("Invalid event: {e}: {text}");
+                        continue;
+                    }
+                };
+                if let Err(QueueError::Closed(_)) =
+                    event_tx.send(InternalEvent::OneBotEvent(event)).await
+                {
+                    return;
+                }
+            }
+            ClientRole::Api => handle_api_return(text, &api_tx_map),
+            ClientRole::Universal => {
+                if !dispatch_universal_frame(text, &event_tx, &api_tx_map).await {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// 检查 `Authorization` 头或 `access_token` 参数，未设置 access_token 时总是通过
+fn is_authorized(request: &Request, access_token: &str) -> bool {
+    if access_token.is_empty() {
+        return true;
+    }
+
+    if let Some(auth) = request
+        .headers()
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+    {
+        let token = auth
+            .strip_prefix("Bearer ")
+            .or_else(|| auth.strip_prefix("Token "))
+            .unwrap_or(auth);
+        return token.trim() == access_token;
+    }
+
+    request
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|kv| kv.split_once('='))
+        .any(|(k, v)| k == "access_token" && v == access_token)
+}
+
+#[test]
+fn reverse_ws_request_check() {
+    let request = Request::builder()
+        .uri("/onebot/v11/ws")
+        .header("Authorization", "Bearer abc")
+        .body(())
+        .unwrap();
+    assert!(is_authorized(&request, "abc"));
+    assert!(!is_authorized(&request, "abd"));
+    assert_eq!(ClientRole::from_request(&request), ClientRole::Universal);
+
+    let request = Request::builder()
+        .uri("/onebot/v11/ws/api?access_token=abc")
+        .body(())
+        .unwrap();
+    assert!(is_authorized(&request, "abc"));
+    assert_eq!(ClientRole::from_request(&request), ClientRole::Api);
+
+    let request = Request::builder()
+        .uri("/ws")
+        .header("X-Client-Role", "Event")
+        .body(())
+        .unwrap();
+    assert!(!is_authorized(&request, "abc"));
+    assert!(is_authorized(&request, ""));
+    assert_eq!(ClientRole::from_request(&request), ClientRole::Event);
+}
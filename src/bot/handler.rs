@@ -1,34 +1,153 @@
+use crate::bot::runtimebot::onebot_api::{parse_data, LoginInfo};
+use crate::bot::runtimebot::{rand_echo, send_api_await_response_timeout, send_api_request};
 use crate::bot::*;
 use log::{debug, error, info, warn};
-#[cfg(feature = "message_sent")]
-use plugin_builder::AllMsgFn;
 use plugin_builder::{
-    event::{MsgEvent, NoticeEvent, RequestEvent},
-    AllNoticeFn, AllRequestFn, ListenMsgFn, NoArgsFn,
+    event::{HeartbeatEvent, LifecycleEvent, MsgEvent, NoticeEvent, QuickOperation, RequestEvent},
+    Listen, ListenMsgFn, NoArgsFn, PinFut,
 };
 use serde_json::{json, Value};
+use std::future::Future;
+use std::sync::atomic::Ordering;
 use std::sync::{Arc, RwLock};
-use tokio::sync::oneshot;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+#[cfg(feature = "bench")]
+mod bench;
+#[cfg(feature = "bench")]
+pub use bench::DispatchBench;
 
 /// Kovi内部事件
 pub enum InternalEvent {
     KoviEvent(KoviEvent),
-    OneBotEvent(String),
+    /// 已解析的 OneBot 事件，每个事件只在连接处解析一次
+    OneBotEvent(Value),
+    /// HTTP POST 上报的事件，附带快速操作的回复通道
+    OneBotHttpPost(Value, oneshot::Sender<Value>),
 }
 
 pub enum KoviEvent {
     Drop,
+    /// 账号的事件连接断开，之后可能会重连
+    Disconnected,
 }
 
 impl Bot {
     pub(crate) async fn handler_event(
         bot: Arc<RwLock<Self>>,
         event: InternalEvent,
-        api_tx: mpsc::Sender<ApiAndOneshot>,
+        account: Arc<BotAccount>,
+    ) {
+        if let Some(event) = Self::handle_account_event(&bot, event, &account).await {
+            Self::dispatch_event(bot, event, account).await
+        }
+    }
+
+    /// 按收到的顺序处理账号连接状态相关的事件：断开、lifecycle、心跳与登录号记录。
+    ///
+    /// 这些事件不能并发处理，否则断开可能在之后的连接之后才处理。插件的监听仍然在各自的任务中运行。
+    /// 返回需要分发给插件的其他事件
+    pub(crate) async fn handle_account_event(
+        bot: &Arc<RwLock<Self>>,
+        event: InternalEvent,
+        account: &Arc<BotAccount>,
+    ) -> Option<InternalEvent> {
+        let msg_json = match &event {
+            InternalEvent::KoviEvent(KoviEvent::Disconnected) => {
+                Self::wait_connecting(account).await;
+                Self::handler_disconnected(account);
+                return None;
+            }
+            InternalEvent::KoviEvent(_) => return Some(event),
+            InternalEvent::OneBotEvent(v) | InternalEvent::OneBotHttpPost(v, _) => v,
+        };
+
+        let meta_event_type = msg_json.get("meta_event_type").and_then(|v| v.as_str());
+        let is_lifecycle = meta_event_type == Some("lifecycle");
+        if let Some(self_id) = msg_json.get("self_id").and_then(|v| v.as_i64()) {
+            // 没有 lifecycle 事件的连接方式（如 HTTP）在第一次收到事件时获取登录号信息
+            if Self::register_account(bot, self_id, account) && !is_lifecycle {
+                tokio::spawn(Self::handler_lifecycle(bot.clone(), account.clone()));
+            }
+        }
+        account.online.store(true, Ordering::SeqCst);
+
+        match meta_event_type {
+            // 生命周期一开始请求bot的信息
+            Some("lifecycle") => {
+                debug!("{msg_json}");
+                let e = LifecycleEvent::new(msg_json.clone());
+                Self::wait_connecting(account).await;
+                if e.sub_type == "disable" {
+                    Self::handler_disconnected(account);
+                    return None;
+                }
+
+                // 获取登录号信息需要等待 api 返回，不阻塞之后的事件
+                let task = tokio::spawn({
+                    let bot = bot.clone();
+                    let account = account.clone();
+                    async move {
+                        Self::handler_lifecycle(bot, account.clone()).await;
+
+                        let e = Arc::new(e);
+                        let (started, mut all_started) = mpsc::channel::<()>(1);
+                        Self::spawn_listeners(
+                            &account.listen.load(),
+                            |l| &l.connected,
+                            |listen| {
+                                let started = started.clone();
+                                let fut = listen(e.clone());
+                                Box::pin(async move {
+                                    drop(started);
+                                    fut.await
+                                })
+                            },
+                        );
+                        drop(started);
+                        // 连接事件的监听都开始运行后，之后的断开事件才会触发
+                        let _ = all_started.recv().await;
+                    }
+                });
+                *account.connecting.lock() = Some(task);
+            }
+            Some("heartbeat") => {
+                debug!("{msg_json}");
+                let e = HeartbeatEvent::new(msg_json.clone());
+                if e.interval > 0 {
+                    account
+                        .heartbeat
+                        .send_replace(Some(Duration::from_millis(e.interval as u64)));
+                }
+
+                let e = Arc::new(e);
+                Self::spawn_listeners(
+                    &account.listen.load(),
+                    |l| &l.heartbeat,
+                    |listen| listen(e.clone()),
+                );
+            }
+            Some(_) => {}
+            None => return Some(event),
+        }
+        None
+    }
+
+    /// 分发消息、通知与请求事件，以及 Bot 级别的事件
+    pub(crate) async fn dispatch_event(
+        bot: Arc<RwLock<Self>>,
+        event: InternalEvent,
+        account: Arc<BotAccount>,
     ) {
         match event {
             InternalEvent::KoviEvent(event) => Self::handle_kovi_event(bot, event).await,
-            InternalEvent::OneBotEvent(msg) => Self::handler_msg(bot, msg, api_tx).await,
+            InternalEvent::OneBotEvent(msg) => {
+                Self::handler_msg(msg, account, QuickOperation::default()).await
+            }
+            InternalEvent::OneBotHttpPost(msg, quick_tx) => {
+                Self::handler_msg(msg, account, QuickOperation::new(quick_tx)).await
+            }
         }
     }
 
@@ -43,8 +162,11 @@ impl Bot {
                     for plugin in bot_write.plugins.values_mut() {
                         task_vec.push(plugin.shutdown());
                     }
+                    bot_write.publish_listen();
                     Some(task_vec)
                 }
+                // 需要账号信息，在 handle_account_event 中处理
+                KoviEvent::Disconnected => None,
             }
         };
         if let Some(drop_task) = drop_task {
@@ -54,38 +176,32 @@ impl Bot {
         }
     }
 
-    async fn handler_msg(bot: Arc<RwLock<Self>>, msg: String, api_tx: mpsc::Sender<ApiAndOneshot>) {
-        let msg_json: Value = serde_json::from_str(&msg).unwrap();
-
+    async fn handler_msg(
+        msg_json: Value,
+        account: Arc<BotAccount>,
+        quick_operation: QuickOperation,
+    ) {
         debug!("{msg_json}");
 
-        if let Some(meta_event_type) = msg_json.get("meta_event_type") {
-            match meta_event_type.as_str().unwrap() {
-                // 生命周期一开始请求bot的信息
-                "lifecycle" => {
-                    Self::handler_lifecycle(api_tx).await;
-                    return;
-                }
-                "heartbeat" => {
-                    return;
-                }
-                _ => {
-                    return;
-                }
-            }
-        }
+        let api_tx = account.api_tx.clone();
 
         enum OneBotEvent {
-            Msg(MsgEvent),
+            Msg(Box<MsgEvent>),
             #[cfg(feature = "message_sent")]
-            MsgSent(MsgEvent),
+            MsgSent(Box<MsgEvent>),
             AllNotice(NoticeEvent),
             AllRequest(RequestEvent),
         }
 
-        let event = match msg_json.get("post_type").unwrap().as_str().unwrap() {
+        let post_type = msg_json
+            .get("post_type")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string();
+        // 把 msg_json 直接交给对应的事件，不再重复解析
+        let event = match post_type.as_str() {
             "message" => {
-                let e = match MsgEvent::new(api_tx, &msg) {
+                let mut e = match MsgEvent::new(api_tx, msg_json) {
                     Ok(event) => event,
                     Err(e) => {
                         error!("{e}");
@@ -102,21 +218,22 @@ impl Bot {
                     None => "".to_string(),
                 };
                 info!("[{message_type}{group_id}{nickname} {id}]: {text}");
-                OneBotEvent::Msg(e)
+                e.quick_operation = quick_operation;
+                OneBotEvent::Msg(Box::new(e))
             }
             #[cfg(feature = "message_sent")]
             "message_sent" => {
-                let e = match MsgEvent::new(api_tx, &msg) {
+                let e = match MsgEvent::new(api_tx, msg_json) {
                     Ok(event) => event,
                     Err(e) => {
                         error!("{e}");
                         return;
                     }
                 };
-                OneBotEvent::MsgSent(e)
+                OneBotEvent::MsgSent(Box::new(e))
             }
             "notice" => {
-                let e = match NoticeEvent::new(&msg) {
+                let e = match NoticeEvent::new(msg_json) {
                     Ok(event) => event,
                     Err(e) => {
                         error!("{e}");
@@ -126,161 +243,71 @@ impl Bot {
                 OneBotEvent::AllNotice(e)
             }
             "request" => {
-                let e = match RequestEvent::new(&msg) {
+                let mut e = match RequestEvent::new(api_tx, msg_json) {
                     Ok(event) => event,
                     Err(e) => {
                         error!("{e}");
                         return;
                     }
                 };
+                e.quick_operation = quick_operation;
                 OneBotEvent::AllRequest(e)
             }
 
             _ => {
-                warn!("Unknown event: {msg}");
+                warn!("Unknown event: {msg_json}");
                 return;
             }
         };
 
-        let bot_read = bot.read().unwrap();
+        // 读取监听快照，分发事件时不需要锁住 Bot
+        let snapshot = account.listen.load();
 
         match event {
             OneBotEvent::Msg(e) => {
-                let e = Arc::new(e);
-                for (name, plugin) in bot_read.plugins.iter() {
+                let e: Arc<MsgEvent> = Arc::from(e);
+                for plugin in snapshot.plugins.iter() {
                     // 判断是否黑白名单
                     #[cfg(feature = "plugin-access-control")]
                     if !is_access(plugin, &e) {
                         continue;
                     }
 
-                    let name_ = Arc::new(name.clone());
-
-                    for listen in &plugin.listen.msg {
-                        let name = name_.clone();
-                        let event_clone = Arc::clone(&e);
-                        let bot_clone = bot.clone();
-                        let listen = listen.clone();
-                        let mut enabled = plugin.enabled.subscribe();
-                        tokio::spawn(async move {
-                            tokio::select! {
-                                _ = PLUGIN_NAME.scope(name, Self::handle_msg(listen, event_clone, bot_clone)) => {}
-                                _ = async {
-                                        loop {
-                                            enabled.changed().await.unwrap();
-                                            if !*enabled.borrow_and_update() {
-                                                break;
-                                            }
-                                        }
-                                } => {}
-                            }
-                        });
-                    }
+                    let futs = plugin
+                        .listen
+                        .msg
+                        .iter()
+                        .map(|listen| {
+                            Self::handle_msg(listen.clone(), e.clone(), snapshot.admins.clone())
+                        })
+                        .collect::<Vec<_>>();
+                    spawn_in_plugin(plugin, futs);
                 }
             }
             #[cfg(feature = "message_sent")]
             OneBotEvent::MsgSent(e) => {
-                let e = Arc::new(e);
-                for (name, plugin) in bot_read.plugins.iter() {
-                    let name_ = Arc::new(name.clone());
-
-                    for listen in &plugin.listen.msg_sent {
-                        let name = name_.clone();
-                        let event_clone = Arc::clone(&e);
-                        let listen = listen.clone();
-                        let mut enabled = plugin.enabled.subscribe();
-
-                        tokio::spawn(async move {
-                            tokio::select! {
-                                _ = PLUGIN_NAME.scope(name, Self::handler_msg_sent(listen,event_clone)) => {}
-                                _ = async {
-                                        loop {
-                                            enabled.changed().await.unwrap();
-                                            if !*enabled.borrow_and_update() {
-                                                break;
-                                            }
-                                        }
-                                } => {}
-                            }
-                        });
-                    }
-                }
+                let e: Arc<MsgEvent> = Arc::from(e);
+                Self::spawn_listeners(&snapshot, |l| &l.msg_sent, |listen| listen(e.clone()));
             }
             OneBotEvent::AllNotice(e) => {
                 let e = Arc::new(e);
-                for (name, plugin) in bot_read.plugins.iter() {
-                    let name_ = Arc::new(name.clone());
-
-                    for listen in &plugin.listen.notice {
-                        let name = name_.clone();
-                        let event_clone = Arc::clone(&e);
-                        let listen = listen.clone();
-                        let mut enabled = plugin.enabled.subscribe();
-
-                        tokio::spawn(async move {
-                            tokio::select! {
-                                _ = PLUGIN_NAME.scope(name, Self::handler_notice(listen, event_clone)) => {}
-                                _ = async {
-                                        loop {
-                                            enabled.changed().await.unwrap();
-                                            if !*enabled.borrow_and_update() {
-                                                break;
-                                            }
-                                        }
-                                } => {}
-                            }
-                        });
-                    }
-                }
+                Self::spawn_listeners(&snapshot, |l| &l.notice, |listen| listen(e.clone()));
             }
             OneBotEvent::AllRequest(e) => {
                 let e = Arc::new(e);
-                for (name, plugin) in bot_read.plugins.iter() {
-                    let name_ = Arc::new(name.clone());
-
-                    for listen in &plugin.listen.request {
-                        let name = name_.clone();
-                        let event_clone = Arc::clone(&e);
-                        let listen = listen.clone();
-                        let mut enabled = plugin.enabled.subscribe();
-
-                        tokio::spawn(async move {
-                            tokio::select! {
-                                _ = PLUGIN_NAME.scope(name, Self::handler_request(listen, event_clone)) => {}
-                                _ = async {
-                                        loop {
-                                            enabled.changed().await.unwrap();
-                                            if !*enabled.borrow_and_update() {
-                                                break;
-                                        }}} => {}
-                            }
-                        });
-                    }
-                }
+                Self::spawn_listeners(&snapshot, |l| &l.request, |listen| listen(e.clone()));
             }
         }
     }
 
-    async fn handle_msg(listen: Arc<ListenMsgFn>, e: Arc<MsgEvent>, bot: Arc<RwLock<Bot>>) {
+    async fn handle_msg(listen: Arc<ListenMsgFn>, e: Arc<MsgEvent>, admins: Arc<HashSet<i64>>) {
         match &*listen {
             ListenMsgFn::Msg(handler) => {
                 handler(e).await;
             }
 
             ListenMsgFn::AdminMsg(handler) => {
-                let user_id = e.user_id;
-                let admin_vec = {
-                    let bot = bot.read().unwrap();
-                    let mut admin_vec = bot
-                        .information
-                        .deputy_admins
-                        .iter()
-                        .cloned()
-                        .collect::<Vec<_>>();
-                    admin_vec.push(bot.information.main_admin);
-                    admin_vec
-                };
-                if admin_vec.contains(&user_id) {
+                if admins.contains(&e.user_id) {
                     handler(e).await;
                 }
             }
@@ -297,35 +324,57 @@ impl Bot {
         }
     }
 
-    #[cfg(feature = "message_sent")]
-    async fn handler_msg_sent(listen: AllMsgFn, e: Arc<MsgEvent>) {
-        listen(e).await;
+    pub(crate) async fn handler_drop(listen: NoArgsFn) {
+        listen().await;
     }
 
-    async fn handler_notice(listen: AllNoticeFn, e: Arc<NoticeEvent>) {
-        listen(e).await;
+    /// 等待进行中的 lifecycle 处理，保证连接事件在之后的断开事件之前触发
+    pub(crate) async fn wait_connecting(account: &BotAccount) {
+        let task = account.connecting.lock().take();
+        if let Some(task) = task {
+            let _ = task.await;
+        }
     }
 
-    async fn handler_request(listen: AllRequestFn, e: Arc<RequestEvent>) {
-        listen(e).await;
+    /// 账号的事件连接断开，每次断开只触发一次
+    pub(crate) fn handler_disconnected(account: &BotAccount) {
+        account.heartbeat.send_replace(None);
+        if !account.online.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        let self_id = account.self_id.load(Ordering::SeqCst);
+        Self::spawn_listeners(
+            &account.listen.load(),
+            |l| &l.disconnected,
+            |listen| listen(self_id),
+        );
     }
 
-    pub(crate) async fn handler_drop(listen: NoArgsFn) {
-        listen().await;
+    /// 在每个插件的任务作用域内运行它的一类监听，插件被禁用时中止
+    pub(crate) fn spawn_listeners<L>(
+        snapshot: &ListenSnapshot,
+        listens: fn(&Listen) -> &Vec<L>,
+        call: impl Fn(&L) -> PinFut,
+    ) {
+        for plugin in snapshot.plugins.iter() {
+            let futs = listens(&plugin.listen)
+                .iter()
+                .map(&call)
+                .collect::<Vec<_>>();
+            spawn_in_plugin(plugin, futs);
+        }
     }
 
-    pub(crate) async fn handler_lifecycle(api_tx_: mpsc::Sender<ApiAndOneshot>) {
-        let api_msg = SendApi::new("get_login_info", json!({}), "kovi");
-
-        #[allow(clippy::type_complexity)]
-        let (api_tx, api_rx): (
-            oneshot::Sender<Result<ApiReturn, ApiReturn>>,
-            oneshot::Receiver<Result<ApiReturn, ApiReturn>>,
-        ) = oneshot::channel();
+    /// 获取登录号信息，记录到 `BotInformation::self_infos`
+    pub(crate) async fn handler_lifecycle(bot: Arc<RwLock<Self>>, account: Arc<BotAccount>) {
+        let api_timeout = bot.read().unwrap().information.api_timeout;
+        let send_api = SendApi::new("get_login_info", json!({}), &rand_echo());
+        let api_rx = send_api_request(&account.api_tx, send_api);
 
-        api_tx_.send((api_msg, Some(api_tx))).await.unwrap();
-
-        let receive = match api_rx.await {
+        let self_info: LoginInfo = match send_api_await_response_timeout(api_rx, api_timeout)
+            .await
+            .and_then(parse_data)
+        {
             Ok(v) => v,
             Err(e) => {
                 error!("Lifecycle Error, get bot info failed: {}", e);
@@ -333,30 +382,50 @@ impl Bot {
             }
         };
 
-        let self_info_value = match receive {
-            Ok(v) => v,
-            Err(e) => {
-                error!("Lifecycle Error, get bot info failed: {}", e);
-                return;
-            }
-        };
-
-        let self_id = self_info_value
-            .data
-            .get("user_id")
-            .unwrap()
-            .as_i64()
-            .unwrap();
-        let self_name = self_info_value.data.get("nickname").unwrap().to_string();
         info!(
             "Bot connection successful，Nickname:{},ID:{}",
-            self_name, self_id
+            self_info.nickname, self_info.user_id
         );
+        bot.write()
+            .unwrap()
+            .information
+            .self_infos
+            .insert(self_info.user_id, self_info);
+    }
+}
+
+/// 在插件的任务作用域内运行同一事件的所有监听，每个监听一个任务，插件被禁用时中止
+///
+/// 监听之间互不影响，一个监听 panic 或阻塞不会取消或拖慢同一插件的其他监听。
+fn spawn_in_plugin<F>(plugin: &PluginListen, futs: Vec<F>)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    for fut in futs {
+        let name = plugin.name.clone();
+        let mut enabled = plugin.enabled.clone();
+
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = PLUGIN_NAME.scope(name, fut) => {}
+                _ = async {
+                        loop {
+                            if enabled.changed().await.is_err() {
+                                // 插件已被移除
+                                break;
+                            }
+                            if !*enabled.borrow_and_update() {
+                                break;
+                            }
+                        }
+                } => {}
+            }
+        });
     }
 }
 
 #[cfg(feature = "plugin-access-control")]
-fn is_access(plugin: &BotPlugin, event: &MsgEvent) -> bool {
+fn is_access(plugin: &PluginListen, event: &MsgEvent) -> bool {
     if !plugin.access_control {
         return true;
     }
@@ -379,3 +448,47 @@ fn is_access(plugin: &BotPlugin, event: &MsgEvent) -> bool {
         }
     }
 }
+
+#[tokio::test]
+async fn listeners_run_independently() {
+    use std::pin::Pin;
+    use tokio::sync::{mpsc, watch};
+
+    let (_enabled_tx, enabled) = watch::channel(true);
+    let plugin = PluginListen {
+        name: Arc::new("test".to_string()),
+        listen: Listen::default(),
+        enabled,
+        #[cfg(feature = "plugin-access-control")]
+        access_control: false,
+        #[cfg(feature = "plugin-access-control")]
+        list_mode: AccessControlMode::WhiteList,
+        #[cfg(feature = "plugin-access-control")]
+        access_list: Default::default(),
+    };
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let done = move || -> PinFut {
+        let tx = tx.clone();
+        Box::pin(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            tx.send(()).unwrap();
+        })
+    };
+    let futs: Vec<Pin<Box<dyn Future<Output = ()> + Send>>> = vec![
+        Box::pin(async { panic!("listener panic") }),
+        // 一直阻塞的监听
+        Box::pin(std::future::pending()),
+        done(),
+        done(),
+    ];
+    spawn_in_plugin(&plugin, futs);
+
+    // 一个监听 panic 或阻塞，不影响其他监听运行完成
+    for _ in 0..2 {
+        tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -0,0 +1,80 @@
+//! 只在 `bench` feature 下编译，供 `benches/dispatch.rs` 使用
+
+use super::*;
+use plugin_builder::AllMsgFn;
+
+/// 事件分发的基准测试使用，通过 `Bot::handler_event` 分发事件。不属于公开 api
+#[doc(hidden)]
+pub struct DispatchBench {
+    bot: Arc<RwLock<Bot>>,
+    account: Arc<BotAccount>,
+    _api_rx: queue::Receiver<ApiAndOneshot>,
+}
+
+impl DispatchBench {
+    /// `plugins` 个插件，每个插件 `listens` 个消息监听与通知监听，监听运行时调用 `f`
+    pub fn new(plugins: usize, listens: usize, f: impl Fn() + Send + Sync + 'static) -> Self {
+        let server = Server::new(
+            Host::Domain("localhost".to_string()),
+            0,
+            String::new(),
+            false,
+        );
+        let mut bot = Bot::build(KoviConf::new(0, None, server, false));
+
+        let f = Arc::new(f);
+        for i in 0..plugins {
+            let name = format!("plugin_{i}");
+            bot.mount_main(
+                name.clone(),
+                "0.0.0".to_string(),
+                Arc::new(|| Box::pin(async {})),
+            );
+            let plugin = bot.plugins.get_mut(&name).unwrap();
+            for _ in 0..listens {
+                let f_ = f.clone();
+                let msg: AllMsgFn = Arc::new(move |_| {
+                    f_();
+                    Box::pin(async {})
+                });
+                plugin.listen.msg.push(Arc::new(ListenMsgFn::Msg(msg)));
+                let f_ = f.clone();
+                plugin.listen.notice.push(Arc::new(move |_| {
+                    f_();
+                    Box::pin(async {})
+                }));
+            }
+        }
+        bot.publish_listen();
+
+        let (api_tx, api_rx) = queue::channel("api", &QueueOptions::default());
+        let account = Arc::new(BotAccount::new(
+            Host::Domain("localhost".to_string()),
+            0,
+            api_tx,
+            queue::channel::<InternalEvent>("event", &QueueOptions::default())
+                .0
+                .monitor(),
+            bot.listen.clone(),
+        ));
+        // 预先记录登录号，分发时不会请求登录号信息
+        account.self_id.store(10001, Ordering::SeqCst);
+        bot.accounts.insert(10001, account.clone());
+
+        DispatchBench {
+            bot: Arc::new(RwLock::new(bot)),
+            account,
+            _api_rx: api_rx,
+        }
+    }
+
+    /// 分发一个 OneBot 事件
+    pub async fn dispatch(&self, event: Value) {
+        Bot::handler_event(
+            self.bot.clone(),
+            InternalEvent::OneBotEvent(event),
+            self.account.clone(),
+        )
+        .await
+    }
+}
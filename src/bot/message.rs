@@ -1,13 +1,17 @@
 use std::ops::Add;
 
-#[cfg(feature = "cqstring")]
-use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
 use crate::error::MessageError;
 
 pub mod add;
+pub mod forward;
+pub mod media;
+pub mod segment;
+
+pub use forward::ForwardMessage;
+pub use segment::SegmentKind;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Segment {
@@ -33,7 +37,8 @@ impl PartialEq for Segment {
 
 /// 消息
 ///
-/// **不保证 data 里的 Value 格式是否正确，需要自行检查**
+/// **不保证 data 里的 Value 格式是否正确，需要自行检查**，
+/// 可以使用 [`Message::kinds`] 按照 OneBot v11 标准解析并检查。
 ///
 /// # Examples
 /// ```
@@ -348,97 +353,163 @@ impl From<Message> for CQMessage {
     }
 }
 
+/// CQ 码的前缀
 #[cfg(feature = "cqstring")]
-pub fn cq_to_arr(message: CQMessage) -> Message {
-    let cq_regex = Regex::new(r"\[CQ:([a-zA-Z]+)(,[^\]]+)?\]").unwrap();
-    let mut result = Vec::new();
+const CQ_PREFIX: &str = "[CQ:";
 
-    let mut last_end = 0;
-
-    for cap in cq_regex.captures_iter(&message.0) {
-        let start = cap.get(0).unwrap().start();
+/// 转义纯文本中的 `&`、`[`、`]`
+#[cfg(feature = "cqstring")]
+pub fn cq_escape_text(text: &str) -> String {
+    cq_escape(text, false)
+}
 
-        // 如果前面有纯文本，添加纯文本部分
-        if start > last_end {
-            let text_segment = &message.0[last_end..start];
-            if !text_segment.is_empty() {
-                result.push(json!({
-                    "type": "text",
-                    "data": {
-                        "text": text_segment
-                    }
-                }));
-            }
-        }
+/// 转义 CQ 码参数中的 `&`、`[`、`]`、`,`
+#[cfg(feature = "cqstring")]
+pub fn cq_escape_param(param: &str) -> String {
+    cq_escape(param, true)
+}
 
-        // 解析 CQ 码
-        let function_name = &cap[1];
-        let mut data = serde_json::Map::new();
-
-        if let Some(params) = cap.get(2) {
-            let params_str = params.as_str().trim_start_matches(',');
-            for param in params_str.split(',') {
-                let mut parts = param.splitn(2, '=');
-                let key = parts.next().unwrap().to_string();
-                let value = parts.next().unwrap_or("").to_string();
-                data.insert(key, Value::String(value));
-            }
+#[cfg(feature = "cqstring")]
+fn cq_escape(s: &str, param: bool) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '[' => result.push_str("&#91;"),
+            ']' => result.push_str("&#93;"),
+            ',' if param => result.push_str("&#44;"),
+            c => result.push(c),
         }
+    }
+    result
+}
 
-        result.push(json!({
-            "type": function_name,
-            "data": data
-        }));
+/// 反转义纯文本或 CQ 码参数，不认识的 `&` 原样保留
+#[cfg(feature = "cqstring")]
+pub fn cq_unescape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        result.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let (c, len) = if rest.starts_with("&amp;") {
+            ('&', 5)
+        } else if rest.starts_with("&#91;") {
+            ('[', 5)
+        } else if rest.starts_with("&#93;") {
+            (']', 5)
+        } else if rest.starts_with("&#44;") {
+            (',', 5)
+        } else {
+            ('&', 1)
+        };
+        result.push(c);
+        rest = &rest[len..];
+    }
+    result.push_str(rest);
+    result
+}
 
-        last_end = cap.get(0).unwrap().end();
+/// 解析 `[CQ:` 与 `]` 之间的内容，格式不正确时返回 `None`
+#[cfg(feature = "cqstring")]
+fn parse_cq_body(body: &str) -> Option<Segment> {
+    // 参数中的 `[` 必须转义，出现时说明这不是一个完整的 CQ 码
+    if body.contains('[') {
+        return None;
     }
+    let mut parts = body.split(',');
+    let type_ = parts.next().filter(|v| !v.is_empty() && !v.contains('='))?;
 
-    // 添加最后一段纯文本
-    if last_end < message.0.len() {
-        let text_segment = &message.0[last_end..];
-        if !text_segment.is_empty() {
-            result.push(json!({
-                "type": "text",
-                "data": {
-                    "text": text_segment
-                }
-            }));
-        }
+    let mut data = serde_json::Map::new();
+    for param in parts {
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+        data.insert(cq_unescape(key), Value::String(cq_unescape(value)));
     }
 
-    Message::from_vec_segment_value(result).unwrap()
+    Some(Segment {
+        type_: cq_unescape(type_),
+        data: Value::Object(data),
+    })
 }
 
+/// 解析 CQ 码字符串。格式不正确的 CQ 码作为纯文本，相邻的纯文本合并为一个消息段
 #[cfg(feature = "cqstring")]
-fn parse_cq_code(item: &Segment) -> String {
-    let mut result = String::new();
-
-    match item.type_.as_str() {
-        "text" => {
-            if let Some(text_data) = item.data.get("text") {
-                if let Some(text_str) = text_data.as_str() {
-                    result.push_str(text_str);
-                }
-            }
+pub fn cq_to_arr(message: CQMessage) -> Message {
+    fn push_text(result: &mut Vec<Segment>, text: &mut String) {
+        if !text.is_empty() {
+            let text = std::mem::take(text);
+            result.push(Segment::new("text", json!({ "text": text })));
         }
-        _ => {
-            let mut params = Vec::new();
-            for (key, value) in item.data.as_object().unwrap().iter() {
-                if let Some(value_str) = value.as_str() {
-                    params.push(format!("{}={}", key, value_str));
+    }
+
+    let mut result = Vec::new();
+    // 已经反转义的纯文本，转义序列中没有 `[`，所以可以在 `[` 处分开反转义
+    let mut text = String::new();
+    let mut rest = message.0.as_str();
+
+    while let Some(start) = rest.find(CQ_PREFIX) {
+        text.push_str(&cq_unescape(&rest[..start]));
+        rest = &rest[start..];
+
+        let segment = rest[CQ_PREFIX.len()..].find(']').and_then(|end| {
+            let body = &rest[CQ_PREFIX.len()..CQ_PREFIX.len() + end];
+            parse_cq_body(body).map(|segment| (segment, CQ_PREFIX.len() + end + 1))
+        });
+
+        match segment {
+            Some((segment, len)) => {
+                if segment.type_ == "text" {
+                    // `[CQ:text,text=...]` 与纯文本相同
+                    let value = segment.data.get("text").and_then(Value::as_str);
+                    text.push_str(value.unwrap_or_default());
+                } else {
+                    push_text(&mut result, &mut text);
+                    result.push(segment);
                 }
+                rest = &rest[len..];
             }
-            if !params.is_empty() {
-                let params_str = params.join(",");
-                result.push_str(&format!("[CQ:{},{}]", item.type_, params_str));
-            } else {
-                result.push_str(&format!("[CQ:{}]", item.type_));
+            None => {
+                text.push('[');
+                rest = &rest[1..];
             }
         }
     }
+    text.push_str(&cq_unescape(rest));
+    push_text(&mut result, &mut text);
+
+    Message(result)
+}
+
+/// 将一个消息段转换为 CQ 码，纯文本只转义
+#[cfg(feature = "cqstring")]
+fn parse_cq_code(item: &Segment) -> String {
+    if item.type_ == "text" {
+        let text = item.data.get("text").and_then(Value::as_str);
+        return cq_escape_text(text.unwrap_or_default());
+    }
+
+    let mut result = format!("{CQ_PREFIX}{}", cq_escape_param(&item.type_));
+    if let Some(data) = item.data.as_object() {
+        for (key, value) in data {
+            let value = match value {
+                Value::String(v) => cq_escape_param(v),
+                Value::Null => continue,
+                // CQ 码的参数都是字符串
+                v => cq_escape_param(&v.to_string()),
+            };
+            result.push(',');
+            result.push_str(&cq_escape_param(key));
+            result.push('=');
+            result.push_str(&value);
+        }
+    }
+    result.push(']');
     result
 }
 
+/// 将消息转换为 CQ 码字符串。
+///
+/// 只包含字符串参数的消息可以通过 [`cq_to_arr`] 无损地转换回来，相邻的纯文本会合并，空的纯文本会被忽略
 #[cfg(feature = "cqstring")]
 pub fn arr_to_cq(message: Message) -> CQMessage {
     let mut result = String::new();
@@ -509,3 +580,72 @@ fn check_msg() {
     assert!(msg1.contains("text"));
     assert!(msg2.contains("text"));
 }
+
+#[cfg(all(test, feature = "cqstring"))]
+mod cq_test {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn segment() -> impl Strategy<Value = Segment> {
+        let special = "[a-z0-9&#;,=\\[\\] 中]{0,12}";
+        prop_oneof![
+            special.prop_map(|text| Segment::new("text", json!({ "text": text }))),
+            (
+                "[a-z][a-z0-9_]{0,8}".prop_filter("not text", |t| t != "text"),
+                prop::collection::btree_map("[a-z_][a-z0-9_]{0,6}", special, 0..4),
+            )
+                .prop_map(|(type_, data)| Segment::new(&type_, json!(data))),
+        ]
+    }
+
+    /// CQ 码中相邻的纯文本无法区分，空的纯文本不会出现
+    fn normalize(msg: Message) -> Message {
+        let mut result: Vec<Segment> = Vec::new();
+        for seg in msg {
+            if seg.type_ != "text" {
+                result.push(seg);
+                continue;
+            }
+            let text = seg.data["text"].as_str().unwrap().to_string();
+            if text.is_empty() {
+                continue;
+            }
+            match result.last_mut() {
+                Some(last) if last.type_ == "text" => {
+                    let merged = last.data["text"].as_str().unwrap().to_string() + &text;
+                    last.data = json!({ "text": merged });
+                }
+                _ => result.push(seg),
+            }
+        }
+        Message(result)
+    }
+
+    proptest! {
+        #[test]
+        fn cq_round_trip(segments in prop::collection::vec(segment(), 0..8)) {
+            let msg = Message(segments);
+            let cq = arr_to_cq(msg.clone());
+            prop_assert_eq!(cq_to_arr(cq), normalize(msg));
+        }
+
+        #[test]
+        fn cq_parse_any(s in "(\\[CQ:|[a-z&#;,=\\[\\]])*") {
+            // 任意字符串都可以解析，解析结果转换回 CQ 码后不变
+            let msg = cq_to_arr(s.as_str().into());
+            let cq = arr_to_cq(msg.clone());
+            prop_assert_eq!(cq_to_arr(cq), msg);
+        }
+    }
+
+    #[test]
+    fn cq_escape() {
+        let msg = cq_to_arr(
+            "a&amp;b&#91;[CQ:image,file=a&#44;b&#93;.jpg,url=x=y][CQ:face_2,id=1][CQ:bad".into(),
+        );
+        assert_eq!(msg[0].data, json!({ "text": "a&b[" }));
+        assert_eq!(msg[1].data, json!({ "file": "a,b].jpg", "url": "x=y" }));
+        assert_eq!(msg[2].type_, "face_2");
+        assert_eq!(msg[3].data, json!({ "text": "[CQ:bad" }));
+    }
+}
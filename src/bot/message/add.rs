@@ -2,10 +2,14 @@ use serde::Serialize;
 use serde_json::{json, Value};
 use std::fmt::Display;
 
+use super::segment::{
+    ContactData, ContactType, ImageData, JsonData, LocationData, MusicData, MusicPlatform,
+    PokeData, RecordData, SegmentKind, ShareData, VideoData, XmlData,
+};
 use super::{Message, Segment};
 
 #[cfg(feature = "cqstring")]
-use super::CQMessage;
+use super::{cq_escape_param, cq_escape_text, CQMessage};
 
 impl Message {
     /// 在消息加上文字
@@ -121,6 +125,254 @@ impl Message {
     }
 }
 
+// 构建消息段，`Message` 与 `CQMessage` 共用
+fn music(platform: MusicPlatform, id: &str) -> SegmentKind {
+    SegmentKind::Music(MusicData {
+        type_: platform.as_str().to_string(),
+        id: Some(id.to_string()),
+        ..Default::default()
+    })
+}
+
+fn custom_music(url: &str, audio: &str, title: &str, image: Option<&str>) -> SegmentKind {
+    SegmentKind::Music(MusicData {
+        type_: "custom".to_string(),
+        url: Some(url.to_string()),
+        audio: Some(audio.to_string()),
+        title: Some(title.to_string()),
+        image: image.map(str::to_string),
+        ..Default::default()
+    })
+}
+
+fn flash_image(file: &str) -> SegmentKind {
+    SegmentKind::Image(ImageData {
+        file: file.to_string(),
+        type_: Some("flash".to_string()),
+        ..Default::default()
+    })
+}
+
+fn record(file: &str) -> SegmentKind {
+    SegmentKind::Record(RecordData {
+        file: file.to_string(),
+        ..Default::default()
+    })
+}
+
+fn video(file: &str) -> SegmentKind {
+    SegmentKind::Video(VideoData {
+        file: file.to_string(),
+        ..Default::default()
+    })
+}
+
+fn poke(type_: i32, id: i32) -> SegmentKind {
+    SegmentKind::Poke(PokeData {
+        type_,
+        id,
+        name: None,
+    })
+}
+
+fn share(url: &str, title: &str) -> SegmentKind {
+    SegmentKind::Share(ShareData {
+        url: url.to_string(),
+        title: title.to_string(),
+        ..Default::default()
+    })
+}
+
+fn location(lat: f64, lon: f64) -> SegmentKind {
+    SegmentKind::Location(LocationData {
+        lat,
+        lon,
+        title: None,
+        content: None,
+    })
+}
+
+impl Message {
+    /// 消息加上按照 OneBot v11 标准构建的消息段
+    pub fn add_kind(mut self, kind: SegmentKind) -> Self {
+        self.push_kind(kind);
+        self
+    }
+
+    /// 消息加上图片，可以设置闪照、缓存、代理与超时
+    ///
+    /// # Examples
+    /// ```
+    /// use kovi::bot::message::{segment::ImageData, Message};
+    ///
+    /// let msg = Message::new().add_image_with(ImageData {
+    ///     file: "https://example.com/a.png".to_string(),
+    ///     cache: Some(false),
+    ///     timeout: Some(10),
+    ///     ..Default::default()
+    /// });
+    /// ```
+    pub fn add_image_with(self, image: ImageData) -> Self {
+        self.add_kind(SegmentKind::Image(image))
+    }
+
+    /// 消息加上闪照
+    pub fn add_flash_image(self, file: &str) -> Self {
+        self.add_kind(flash_image(file))
+    }
+
+    /// 消息加上语音，一条消息中只能有一个语音
+    pub fn add_record(self, file: &str) -> Self {
+        self.add_kind(record(file))
+    }
+
+    /// 消息加上短视频
+    pub fn add_video(self, file: &str) -> Self {
+        self.add_kind(video(file))
+    }
+
+    /// 消息加上戳一戳, 具体 type 与 id 请看服务端文档
+    pub fn add_poke(self, type_: i32, id: i32) -> Self {
+        self.add_kind(poke(type_, id))
+    }
+
+    /// 消息加上音乐分享
+    pub fn add_music(self, platform: MusicPlatform, id: &str) -> Self {
+        self.add_kind(music(platform, id))
+    }
+
+    /// 消息加上自定义音乐分享，`url` 为点击后跳转的地址，`audio` 为音乐地址
+    pub fn add_custom_music(
+        self,
+        url: &str,
+        audio: &str,
+        title: &str,
+        image: Option<&str>,
+    ) -> Self {
+        self.add_kind(custom_music(url, audio, title, image))
+    }
+
+    /// 消息加上链接分享
+    pub fn add_share(self, url: &str, title: &str) -> Self {
+        self.add_kind(share(url, title))
+    }
+
+    /// 消息加上推荐好友或群
+    pub fn add_contact(self, type_: ContactType, id: i64) -> Self {
+        self.add_kind(SegmentKind::Contact(ContactData { type_, id }))
+    }
+
+    /// 消息加上位置，`lat` 为纬度，`lon` 为经度
+    pub fn add_location(self, lat: f64, lon: f64) -> Self {
+        self.add_kind(location(lat, lon))
+    }
+
+    /// 消息加上掷骰子
+    pub fn add_dice(self) -> Self {
+        self.add_kind(SegmentKind::Dice)
+    }
+
+    /// 消息加上猜拳
+    pub fn add_rps(self) -> Self {
+        self.add_kind(SegmentKind::Rps)
+    }
+
+    /// 消息加上 JSON 卡片
+    pub fn add_json(self, data: &str) -> Self {
+        self.add_kind(SegmentKind::Json(JsonData {
+            data: data.to_string(),
+        }))
+    }
+
+    /// 消息加上 XML 卡片
+    pub fn add_xml(self, data: &str) -> Self {
+        self.add_kind(SegmentKind::Xml(XmlData {
+            data: data.to_string(),
+        }))
+    }
+}
+
+impl Message {
+    /// 消息加上按照 OneBot v11 标准构建的消息段
+    pub fn push_kind(&mut self, kind: SegmentKind) {
+        self.0.push(kind.into());
+    }
+
+    /// 消息加上图片，可以设置闪照、缓存、代理与超时
+    pub fn push_image_with(&mut self, image: ImageData) {
+        self.push_kind(SegmentKind::Image(image));
+    }
+
+    /// 消息加上闪照
+    pub fn push_flash_image(&mut self, file: &str) {
+        self.push_kind(flash_image(file));
+    }
+
+    /// 消息加上语音，一条消息中只能有一个语音
+    pub fn push_record(&mut self, file: &str) {
+        self.push_kind(record(file));
+    }
+
+    /// 消息加上短视频
+    pub fn push_video(&mut self, file: &str) {
+        self.push_kind(video(file));
+    }
+
+    /// 消息加上戳一戳, 具体 type 与 id 请看服务端文档
+    pub fn push_poke(&mut self, type_: i32, id: i32) {
+        self.push_kind(poke(type_, id));
+    }
+
+    /// 消息加上音乐分享
+    pub fn push_music(&mut self, platform: MusicPlatform, id: &str) {
+        self.push_kind(music(platform, id));
+    }
+
+    /// 消息加上自定义音乐分享，`url` 为点击后跳转的地址，`audio` 为音乐地址
+    pub fn push_custom_music(&mut self, url: &str, audio: &str, title: &str, image: Option<&str>) {
+        self.push_kind(custom_music(url, audio, title, image));
+    }
+
+    /// 消息加上链接分享
+    pub fn push_share(&mut self, url: &str, title: &str) {
+        self.push_kind(share(url, title));
+    }
+
+    /// 消息加上推荐好友或群
+    pub fn push_contact(&mut self, type_: ContactType, id: i64) {
+        self.push_kind(SegmentKind::Contact(ContactData { type_, id }));
+    }
+
+    /// 消息加上位置，`lat` 为纬度，`lon` 为经度
+    pub fn push_location(&mut self, lat: f64, lon: f64) {
+        self.push_kind(location(lat, lon));
+    }
+
+    /// 消息加上掷骰子
+    pub fn push_dice(&mut self) {
+        self.push_kind(SegmentKind::Dice);
+    }
+
+    /// 消息加上猜拳
+    pub fn push_rps(&mut self) {
+        self.push_kind(SegmentKind::Rps);
+    }
+
+    /// 消息加上 JSON 卡片
+    pub fn push_json(&mut self, data: &str) {
+        self.push_kind(SegmentKind::Json(JsonData {
+            data: data.to_string(),
+        }));
+    }
+
+    /// 消息加上 XML 卡片
+    pub fn push_xml(&mut self, data: &str) {
+        self.push_kind(SegmentKind::Xml(XmlData {
+            data: data.to_string(),
+        }));
+    }
+}
+
 #[cfg(feature = "cqstring")]
 impl CQMessage {
     /// 在消息加上文字
@@ -129,13 +381,14 @@ impl CQMessage {
         String: From<T>,
         T: Serialize + Display,
     {
-        self.0.push_str(&format!("[CQ:text,text={}]", text));
+        self.0.push_str(&cq_escape_text(&text.to_string()));
         self
     }
 
     /// 消息加上at
     pub fn add_at(mut self, id: &str) -> Self {
-        self.0.push_str(&format!("[CQ:at,qq={}]", id));
+        self.0
+            .push_str(&format!("[CQ:at,qq={}]", cq_escape_param(id)));
         self
     }
 
@@ -154,7 +407,8 @@ impl CQMessage {
 
     /// 消息加上图片
     pub fn add_image(mut self, file: &str) -> Self {
-        self.0.push_str(&format!("[CQ:image,file={}]", file));
+        self.0
+            .push_str(&format!("[CQ:image,file={}]", cq_escape_param(file)));
         self
     }
 
@@ -180,12 +434,13 @@ impl CQMessage {
         String: From<T>,
         T: Serialize + Display,
     {
-        self.0.push_str(&format!("[CQ:text,text={}]", text));
+        self.0.push_str(&cq_escape_text(&text.to_string()));
     }
 
     /// 消息加上at
     pub fn push_at(&mut self, id: &str) {
-        self.0.push_str(&format!("[CQ:at,qq={}]", id));
+        self.0
+            .push_str(&format!("[CQ:at,qq={}]", cq_escape_param(id)));
     }
 
     /// 消息加上引用
@@ -201,6 +456,199 @@ impl CQMessage {
 
     /// 消息加上图片
     pub fn push_image(&mut self, file: &str) {
-        self.0.push_str(&format!("[CQ:image,file={}]", file));
+        self.0
+            .push_str(&format!("[CQ:image,file={}]", cq_escape_param(file)));
+    }
+}
+
+#[cfg(feature = "cqstring")]
+impl CQMessage {
+    /// 消息加上按照 OneBot v11 标准构建的消息段
+    pub fn add_kind(mut self, kind: SegmentKind) -> Self {
+        self.push_kind(kind);
+        self
+    }
+
+    /// 消息加上图片，可以设置闪照、缓存、代理与超时
+    pub fn add_image_with(self, image: ImageData) -> Self {
+        self.add_kind(SegmentKind::Image(image))
+    }
+
+    /// 消息加上闪照
+    pub fn add_flash_image(self, file: &str) -> Self {
+        self.add_kind(flash_image(file))
+    }
+
+    /// 消息加上语音，一条消息中只能有一个语音
+    pub fn add_record(self, file: &str) -> Self {
+        self.add_kind(record(file))
+    }
+
+    /// 消息加上短视频
+    pub fn add_video(self, file: &str) -> Self {
+        self.add_kind(video(file))
+    }
+
+    /// 消息加上戳一戳
+    pub fn add_poke(self, type_: i32, id: i32) -> Self {
+        self.add_kind(poke(type_, id))
+    }
+
+    /// 消息加上音乐分享
+    pub fn add_music(self, platform: MusicPlatform, id: &str) -> Self {
+        self.add_kind(music(platform, id))
+    }
+
+    /// 消息加上自定义音乐分享
+    pub fn add_custom_music(
+        self,
+        url: &str,
+        audio: &str,
+        title: &str,
+        image: Option<&str>,
+    ) -> Self {
+        self.add_kind(custom_music(url, audio, title, image))
+    }
+
+    /// 消息加上链接分享
+    pub fn add_share(self, url: &str, title: &str) -> Self {
+        self.add_kind(share(url, title))
+    }
+
+    /// 消息加上推荐好友或群
+    pub fn add_contact(self, type_: ContactType, id: i64) -> Self {
+        self.add_kind(SegmentKind::Contact(ContactData { type_, id }))
+    }
+
+    /// 消息加上位置
+    pub fn add_location(self, lat: f64, lon: f64) -> Self {
+        self.add_kind(location(lat, lon))
+    }
+
+    /// 消息加上掷骰子
+    pub fn add_dice(self) -> Self {
+        self.add_kind(SegmentKind::Dice)
+    }
+
+    /// 消息加上猜拳
+    pub fn add_rps(self) -> Self {
+        self.add_kind(SegmentKind::Rps)
+    }
+
+    /// 消息加上 JSON 卡片
+    pub fn add_json(self, data: &str) -> Self {
+        self.add_kind(SegmentKind::Json(JsonData {
+            data: data.to_string(),
+        }))
     }
+
+    /// 消息加上 XML 卡片
+    pub fn add_xml(self, data: &str) -> Self {
+        self.add_kind(SegmentKind::Xml(XmlData {
+            data: data.to_string(),
+        }))
+    }
+}
+
+#[cfg(feature = "cqstring")]
+impl CQMessage {
+    /// 消息加上按照 OneBot v11 标准构建的消息段
+    pub fn push_kind(&mut self, kind: SegmentKind) {
+        self.0.push_str(&super::parse_cq_code(&kind.into()));
+    }
+
+    /// 消息加上图片，可以设置闪照、缓存、代理与超时
+    pub fn push_image_with(&mut self, image: ImageData) {
+        self.push_kind(SegmentKind::Image(image));
+    }
+
+    /// 消息加上闪照
+    pub fn push_flash_image(&mut self, file: &str) {
+        self.push_kind(flash_image(file));
+    }
+
+    /// 消息加上语音，一条消息中只能有一个语音
+    pub fn push_record(&mut self, file: &str) {
+        self.push_kind(record(file));
+    }
+
+    /// 消息加上短视频
+    pub fn push_video(&mut self, file: &str) {
+        self.push_kind(video(file));
+    }
+
+    /// 消息加上戳一戳
+    pub fn push_poke(&mut self, type_: i32, id: i32) {
+        self.push_kind(poke(type_, id));
+    }
+
+    /// 消息加上音乐分享
+    pub fn push_music(&mut self, platform: MusicPlatform, id: &str) {
+        self.push_kind(music(platform, id));
+    }
+
+    /// 消息加上自定义音乐分享
+    pub fn push_custom_music(&mut self, url: &str, audio: &str, title: &str, image: Option<&str>) {
+        self.push_kind(custom_music(url, audio, title, image));
+    }
+
+    /// 消息加上链接分享
+    pub fn push_share(&mut self, url: &str, title: &str) {
+        self.push_kind(share(url, title));
+    }
+
+    /// 消息加上推荐好友或群
+    pub fn push_contact(&mut self, type_: ContactType, id: i64) {
+        self.push_kind(SegmentKind::Contact(ContactData { type_, id }));
+    }
+
+    /// 消息加上位置
+    pub fn push_location(&mut self, lat: f64, lon: f64) {
+        self.push_kind(location(lat, lon));
+    }
+
+    /// 消息加上掷骰子
+    pub fn push_dice(&mut self) {
+        self.push_kind(SegmentKind::Dice);
+    }
+
+    /// 消息加上猜拳
+    pub fn push_rps(&mut self) {
+        self.push_kind(SegmentKind::Rps);
+    }
+
+    /// 消息加上 JSON 卡片
+    pub fn push_json(&mut self, data: &str) {
+        self.push_kind(SegmentKind::Json(JsonData {
+            data: data.to_string(),
+        }));
+    }
+
+    /// 消息加上 XML 卡片
+    pub fn push_xml(&mut self, data: &str) {
+        self.push_kind(SegmentKind::Xml(XmlData {
+            data: data.to_string(),
+        }));
+    }
+}
+
+#[test]
+fn rich_segments() {
+    let msg = Message::new()
+        .add_record("file:///tmp/a.silk")
+        .add_music(MusicPlatform::NetEase, "28949129")
+        .add_custom_music("https://a", "https://a.mp3", "歌", None)
+        .add_location(39.8969, 116.3109)
+        .add_contact(ContactType::Group, 123)
+        .add_flash_image("a.jpg")
+        .add_dice();
+
+    assert_eq!(msg[0].data, json!({ "file": "file:///tmp/a.silk" }));
+    assert_eq!(msg[1].data, json!({ "type": "163", "id": "28949129" }));
+    assert_eq!(msg[3].data, json!({ "lat": "39.8969", "lon": "116.3109" }));
+    assert_eq!(msg[4].data, json!({ "type": "group", "id": "123" }));
+    assert_eq!(msg[5].data, json!({ "file": "a.jpg", "type": "flash" }));
+    assert_eq!(msg[6].type_, "dice");
+    // 构建的消息段都符合标准
+    assert!(msg.kinds().is_ok());
 }
@@ -0,0 +1,144 @@
+//! 合并转发消息
+//!
+//! 通过 `RuntimeBot::send_group_forward_msg` 与 `RuntimeBot::send_private_forward_msg` 发送。
+
+use super::segment::{NodeData, SegmentKind};
+use super::{Message, Segment};
+use serde::{Serialize, Serializer};
+
+/// 合并转发消息，由自定义节点与已有消息的引用组成
+///
+/// 节点的内容可以是另一个合并转发消息，即嵌套的合并转发。
+///
+/// # Examples
+/// ```
+/// use kovi::bot::message::{ForwardMessage, Message};
+///
+/// let inner = ForwardMessage::new().add_node(10001, "某人", "第一页");
+///
+/// let forward = ForwardMessage::new()
+///     .add_node(10001, "某人", Message::from("排行榜").add_text("\n1. Kovi"))
+///     .add_message(123456)
+///     .add_node(10001, "某人", inner);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ForwardMessage(Vec<NodeData>);
+
+impl ForwardMessage {
+    /// 返回空的合并转发消息
+    pub fn new() -> ForwardMessage {
+        ForwardMessage(Vec::new())
+    }
+
+    /// 加上自定义节点，`user_id` 与 `nickname` 为显示的发送者
+    pub fn add_node<T>(mut self, user_id: i64, nickname: &str, content: T) -> Self
+    where
+        Message: From<T>,
+    {
+        self.push_node(user_id, nickname, content);
+        self
+    }
+
+    /// 加上已有的消息
+    pub fn add_message(mut self, message_id: i32) -> Self {
+        self.push_message(message_id);
+        self
+    }
+
+    /// 加上自定义节点，`user_id` 与 `nickname` 为显示的发送者
+    pub fn push_node<T>(&mut self, user_id: i64, nickname: &str, content: T)
+    where
+        Message: From<T>,
+    {
+        self.0.push(NodeData {
+            id: None,
+            user_id: Some(user_id),
+            nickname: Some(nickname.to_string()),
+            content: Some(Message::from(content)),
+        });
+    }
+
+    /// 加上已有的消息
+    pub fn push_message(&mut self, message_id: i32) {
+        self.0.push(NodeData {
+            id: Some(message_id.to_string()),
+            ..Default::default()
+        });
+    }
+
+    /// 节点的数量
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, NodeData> {
+        self.0.iter()
+    }
+}
+
+impl From<ForwardMessage> for Message {
+    fn from(v: ForwardMessage) -> Self {
+        v.0.into_iter().map(SegmentKind::Node).collect()
+    }
+}
+
+impl From<ForwardMessage> for Vec<Segment> {
+    fn from(v: ForwardMessage) -> Self {
+        Message::from(v).into()
+    }
+}
+
+impl Serialize for ForwardMessage {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        Message::from(self.clone()).serialize(s)
+    }
+}
+
+#[test]
+fn forward_message() {
+    use serde_json::json;
+
+    let inner = ForwardMessage::new().add_node(2, "b", "inner");
+    let forward = ForwardMessage::new()
+        .add_node(1, "a", "hi")
+        .add_message(123)
+        .add_node(1, "a", inner);
+
+    assert_eq!(
+        serde_json::to_value(&forward).unwrap(),
+        json!([
+            {
+                "type": "node",
+                "data": {
+                    "user_id": "1",
+                    "nickname": "a",
+                    "content": [{ "type": "text", "data": { "text": "hi" } }]
+                }
+            },
+            { "type": "node", "data": { "id": "123" } },
+            {
+                "type": "node",
+                "data": {
+                    "user_id": "1",
+                    "nickname": "a",
+                    "content": [{
+                        "type": "node",
+                        "data": {
+                            "user_id": "2",
+                            "nickname": "b",
+                            "content": [{ "type": "text", "data": { "text": "inner" } }]
+                        }
+                    }]
+                }
+            }
+        ])
+    );
+
+    // 可以解析回节点
+    let kinds = Message::from(forward).kinds().unwrap();
+    assert!(matches!(&kinds[1], SegmentKind::Node(node) if node.id.as_deref() == Some("123")));
+}
@@ -0,0 +1,460 @@
+//! 从本地文件、内存中的数据或 `Read` 构建图片、语音与短视频消息段。
+//!
+//! 内存中的数据编码为 `base64://`，本地文件转换为 `file:///` 地址，由 OneBot 服务端读取。
+//! 超过大小限制的媒体返回 `MessageError::MediaTooLarge`。不带 `_with_limit` 的方法使用
+//! [`DEFAULT_SIZE_LIMIT`]，配置文件中的 `media_size_limit` 可以通过
+//! `RuntimeBot::get_media_size_limit` 获取后传入 `_with_limit` 方法。
+
+use super::segment::{ImageData, RecordData, SegmentKind, VideoData};
+use super::Message;
+use crate::error::MessageError;
+use base64::Engine;
+use std::io::Read;
+use std::path::Path;
+
+/// 默认的媒体大小限制，30 MiB
+pub const DEFAULT_SIZE_LIMIT: u64 = 30 * 1024 * 1024;
+
+fn check_size(size: u64, limit: u64) -> Result<(), MessageError> {
+    match limit {
+        limit if limit > 0 && size > limit => Err(MessageError::MediaTooLarge { size, limit }),
+        _ => Ok(()),
+    }
+}
+
+/// 编码为 `base64://` 地址
+fn from_bytes(bytes: &[u8], limit: u64) -> Result<String, MessageError> {
+    check_size(bytes.len() as u64, limit)?;
+    let mut file = String::from("base64://");
+    base64::engine::general_purpose::STANDARD.encode_string(bytes, &mut file);
+    Ok(file)
+}
+
+/// 读取到结尾后编码为 `base64://` 地址，超过大小限制时不再继续读取
+fn from_reader(reader: impl Read, limit: u64) -> Result<String, MessageError> {
+    let mut bytes = Vec::new();
+    let read = match limit {
+        0 => reader.take(u64::MAX).read_to_end(&mut bytes),
+        // 多读一个字节，用于判断是否超过限制。超过时报告的大小为 `limit + 1`
+        limit => reader.take(limit + 1).read_to_end(&mut bytes),
+    };
+    read.map_err(|e| MessageError::MediaReadError(e.to_string()))?;
+    from_bytes(&bytes, limit)
+}
+
+/// 检查文件可以读取，转换为 `file:///` 地址
+fn from_path(path: &Path, limit: u64) -> Result<String, MessageError> {
+    let read_error =
+        |e: std::io::Error| MessageError::MediaReadError(format!("{}: {e}", path.display()));
+
+    let path = path.canonicalize().map_err(read_error)?;
+    let file = std::fs::File::open(&path).map_err(read_error)?;
+    let metadata = file.metadata().map_err(read_error)?;
+    if !metadata.is_file() {
+        return Err(MessageError::MediaReadError(format!(
+            "{}: not a file",
+            path.display()
+        )));
+    }
+    check_size(metadata.len(), limit)?;
+
+    Ok(file_uri(&path))
+}
+
+/// 转换为 `file:///` 地址，路径的每一段都经过百分号编码
+fn file_uri(path: &Path) -> String {
+    let path = path.as_os_str().as_encoded_bytes();
+    // Windows 上 canonicalize 的结果以 `\\?\` 开头
+    let path = path.strip_prefix(br"\\?\").unwrap_or(path);
+
+    let mut uri = String::from("file://");
+    for component in path.split(|&b| b == b'/' || (cfg!(windows) && b == b'\\')) {
+        if component.is_empty() {
+            continue;
+        }
+        uri.push('/');
+        for &b in component {
+            match b {
+                // 保留 Windows 盘符中的 `:`
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b':' => {
+                    uri.push(b as char)
+                }
+                b => uri.push_str(&format!("%{b:02X}")),
+            }
+        }
+    }
+    if uri.len() == "file://".len() {
+        uri.push('/');
+    }
+    uri
+}
+
+fn image(file: String) -> SegmentKind {
+    SegmentKind::Image(ImageData {
+        file,
+        ..Default::default()
+    })
+}
+
+fn record(file: String) -> SegmentKind {
+    SegmentKind::Record(RecordData {
+        file,
+        ..Default::default()
+    })
+}
+
+fn video(file: String) -> SegmentKind {
+    SegmentKind::Video(VideoData {
+        file,
+        ..Default::default()
+    })
+}
+
+impl Message {
+    /// 消息加上内存中的图片，以 base64 发送
+    ///
+    /// # Error
+    ///
+    /// 超过大小限制时返回 `MessageError::MediaTooLarge`
+    ///
+    /// # Examples
+    /// ```
+    /// use kovi::bot::message::Message;
+    ///
+    /// let png: Vec<u8> = vec![0x89, 0x50, 0x4E, 0x47];
+    /// let msg = Message::new()
+    ///     .add_text("渲染结果")
+    ///     .add_image_bytes(&png)
+    ///     .unwrap();
+    /// ```
+    pub fn add_image_bytes(self, bytes: impl AsRef<[u8]>) -> Result<Self, MessageError> {
+        self.add_image_bytes_with_limit(bytes, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// 消息加上内存中的图片，以 base64 发送，使用 `limit` 作为大小限制，单位字节，0 为不限制
+    pub fn add_image_bytes_with_limit(
+        mut self,
+        bytes: impl AsRef<[u8]>,
+        limit: u64,
+    ) -> Result<Self, MessageError> {
+        self.push_image_bytes_with_limit(bytes, limit)?;
+        Ok(self)
+    }
+
+    /// 消息加上本地图片，OneBot 服务端需要能读取此路径
+    ///
+    /// # Error
+    ///
+    /// 无法读取文件时返回 `MessageError::MediaReadError`，超过大小限制时返回 `MessageError::MediaTooLarge`
+    pub fn add_image_path(self, path: impl AsRef<Path>) -> Result<Self, MessageError> {
+        self.add_image_path_with_limit(path, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// 消息加上本地图片，OneBot 服务端需要能读取此路径，使用 `limit` 作为大小限制，单位字节，0 为不限制
+    pub fn add_image_path_with_limit(
+        mut self,
+        path: impl AsRef<Path>,
+        limit: u64,
+    ) -> Result<Self, MessageError> {
+        self.push_image_path_with_limit(path, limit)?;
+        Ok(self)
+    }
+
+    /// 读取图片，以 base64 发送
+    ///
+    /// # Error
+    ///
+    /// 读取失败时返回 `MessageError::MediaReadError`，超过大小限制时返回 `MessageError::MediaTooLarge`
+    pub fn add_image_reader(self, reader: impl Read) -> Result<Self, MessageError> {
+        self.add_image_reader_with_limit(reader, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// 读取图片，以 base64 发送，使用 `limit` 作为大小限制，单位字节，0 为不限制
+    pub fn add_image_reader_with_limit(
+        mut self,
+        reader: impl Read,
+        limit: u64,
+    ) -> Result<Self, MessageError> {
+        self.push_image_reader_with_limit(reader, limit)?;
+        Ok(self)
+    }
+
+    /// 消息加上内存中的语音，以 base64 发送
+    pub fn add_record_bytes(self, bytes: impl AsRef<[u8]>) -> Result<Self, MessageError> {
+        self.add_record_bytes_with_limit(bytes, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// 消息加上内存中的语音，以 base64 发送，使用 `limit` 作为大小限制，单位字节，0 为不限制
+    pub fn add_record_bytes_with_limit(
+        mut self,
+        bytes: impl AsRef<[u8]>,
+        limit: u64,
+    ) -> Result<Self, MessageError> {
+        self.push_record_bytes_with_limit(bytes, limit)?;
+        Ok(self)
+    }
+
+    /// 消息加上本地语音，OneBot 服务端需要能读取此路径
+    pub fn add_record_path(self, path: impl AsRef<Path>) -> Result<Self, MessageError> {
+        self.add_record_path_with_limit(path, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// 消息加上本地语音，OneBot 服务端需要能读取此路径，使用 `limit` 作为大小限制，单位字节，0 为不限制
+    pub fn add_record_path_with_limit(
+        mut self,
+        path: impl AsRef<Path>,
+        limit: u64,
+    ) -> Result<Self, MessageError> {
+        self.push_record_path_with_limit(path, limit)?;
+        Ok(self)
+    }
+
+    /// 读取语音，以 base64 发送
+    pub fn add_record_reader(self, reader: impl Read) -> Result<Self, MessageError> {
+        self.add_record_reader_with_limit(reader, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// 读取语音，以 base64 发送，使用 `limit` 作为大小限制，单位字节，0 为不限制
+    pub fn add_record_reader_with_limit(
+        mut self,
+        reader: impl Read,
+        limit: u64,
+    ) -> Result<Self, MessageError> {
+        self.push_record_reader_with_limit(reader, limit)?;
+        Ok(self)
+    }
+
+    /// 消息加上内存中的短视频，以 base64 发送
+    pub fn add_video_bytes(self, bytes: impl AsRef<[u8]>) -> Result<Self, MessageError> {
+        self.add_video_bytes_with_limit(bytes, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// 消息加上内存中的短视频，以 base64 发送，使用 `limit` 作为大小限制，单位字节，0 为不限制
+    pub fn add_video_bytes_with_limit(
+        mut self,
+        bytes: impl AsRef<[u8]>,
+        limit: u64,
+    ) -> Result<Self, MessageError> {
+        self.push_video_bytes_with_limit(bytes, limit)?;
+        Ok(self)
+    }
+
+    /// 消息加上本地短视频，OneBot 服务端需要能读取此路径
+    pub fn add_video_path(self, path: impl AsRef<Path>) -> Result<Self, MessageError> {
+        self.add_video_path_with_limit(path, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// 消息加上本地短视频，OneBot 服务端需要能读取此路径，使用 `limit` 作为大小限制，单位字节，0 为不限制
+    pub fn add_video_path_with_limit(
+        mut self,
+        path: impl AsRef<Path>,
+        limit: u64,
+    ) -> Result<Self, MessageError> {
+        self.push_video_path_with_limit(path, limit)?;
+        Ok(self)
+    }
+
+    /// 读取短视频，以 base64 发送
+    pub fn add_video_reader(self, reader: impl Read) -> Result<Self, MessageError> {
+        self.add_video_reader_with_limit(reader, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// 读取短视频，以 base64 发送，使用 `limit` 作为大小限制，单位字节，0 为不限制
+    pub fn add_video_reader_with_limit(
+        mut self,
+        reader: impl Read,
+        limit: u64,
+    ) -> Result<Self, MessageError> {
+        self.push_video_reader_with_limit(reader, limit)?;
+        Ok(self)
+    }
+}
+
+impl Message {
+    /// 消息加上内存中的图片，以 base64 发送
+    pub fn push_image_bytes(&mut self, bytes: impl AsRef<[u8]>) -> Result<(), MessageError> {
+        self.push_image_bytes_with_limit(bytes, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// 消息加上内存中的图片，以 base64 发送，使用 `limit` 作为大小限制，单位字节，0 为不限制
+    pub fn push_image_bytes_with_limit(
+        &mut self,
+        bytes: impl AsRef<[u8]>,
+        limit: u64,
+    ) -> Result<(), MessageError> {
+        self.push_kind(image(from_bytes(bytes.as_ref(), limit)?));
+        Ok(())
+    }
+
+    /// 消息加上本地图片，OneBot 服务端需要能读取此路径
+    pub fn push_image_path(&mut self, path: impl AsRef<Path>) -> Result<(), MessageError> {
+        self.push_image_path_with_limit(path, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// 消息加上本地图片，OneBot 服务端需要能读取此路径，使用 `limit` 作为大小限制，单位字节，0 为不限制
+    pub fn push_image_path_with_limit(
+        &mut self,
+        path: impl AsRef<Path>,
+        limit: u64,
+    ) -> Result<(), MessageError> {
+        self.push_kind(image(from_path(path.as_ref(), limit)?));
+        Ok(())
+    }
+
+    /// 读取图片，以 base64 发送
+    pub fn push_image_reader(&mut self, reader: impl Read) -> Result<(), MessageError> {
+        self.push_image_reader_with_limit(reader, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// 读取图片，以 base64 发送，使用 `limit` 作为大小限制，单位字节，0 为不限制
+    pub fn push_image_reader_with_limit(
+        &mut self,
+        reader: impl Read,
+        limit: u64,
+    ) -> Result<(), MessageError> {
+        self.push_kind(image(from_reader(reader, limit)?));
+        Ok(())
+    }
+
+    /// 消息加上内存中的语音，以 base64 发送
+    pub fn push_record_bytes(&mut self, bytes: impl AsRef<[u8]>) -> Result<(), MessageError> {
+        self.push_record_bytes_with_limit(bytes, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// 消息加上内存中的语音，以 base64 发送，使用 `limit` 作为大小限制，单位字节，0 为不限制
+    pub fn push_record_bytes_with_limit(
+        &mut self,
+        bytes: impl AsRef<[u8]>,
+        limit: u64,
+    ) -> Result<(), MessageError> {
+        self.push_kind(record(from_bytes(bytes.as_ref(), limit)?));
+        Ok(())
+    }
+
+    /// 消息加上本地语音，OneBot 服务端需要能读取此路径
+    pub fn push_record_path(&mut self, path: impl AsRef<Path>) -> Result<(), MessageError> {
+        self.push_record_path_with_limit(path, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// 消息加上本地语音，OneBot 服务端需要能读取此路径，使用 `limit` 作为大小限制，单位字节，0 为不限制
+    pub fn push_record_path_with_limit(
+        &mut self,
+        path: impl AsRef<Path>,
+        limit: u64,
+    ) -> Result<(), MessageError> {
+        self.push_kind(record(from_path(path.as_ref(), limit)?));
+        Ok(())
+    }
+
+    /// 读取语音，以 base64 发送
+    pub fn push_record_reader(&mut self, reader: impl Read) -> Result<(), MessageError> {
+        self.push_record_reader_with_limit(reader, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// 读取语音，以 base64 发送，使用 `limit` 作为大小限制，单位字节，0 为不限制
+    pub fn push_record_reader_with_limit(
+        &mut self,
+        reader: impl Read,
+        limit: u64,
+    ) -> Result<(), MessageError> {
+        self.push_kind(record(from_reader(reader, limit)?));
+        Ok(())
+    }
+
+    /// 消息加上内存中的短视频，以 base64 发送
+    pub fn push_video_bytes(&mut self, bytes: impl AsRef<[u8]>) -> Result<(), MessageError> {
+        self.push_video_bytes_with_limit(bytes, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// 消息加上内存中的短视频，以 base64 发送，使用 `limit` 作为大小限制，单位字节，0 为不限制
+    pub fn push_video_bytes_with_limit(
+        &mut self,
+        bytes: impl AsRef<[u8]>,
+        limit: u64,
+    ) -> Result<(), MessageError> {
+        self.push_kind(video(from_bytes(bytes.as_ref(), limit)?));
+        Ok(())
+    }
+
+    /// 消息加上本地短视频，OneBot 服务端需要能读取此路径
+    pub fn push_video_path(&mut self, path: impl AsRef<Path>) -> Result<(), MessageError> {
+        self.push_video_path_with_limit(path, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// 消息加上本地短视频，OneBot 服务端需要能读取此路径，使用 `limit` 作为大小限制，单位字节，0 为不限制
+    pub fn push_video_path_with_limit(
+        &mut self,
+        path: impl AsRef<Path>,
+        limit: u64,
+    ) -> Result<(), MessageError> {
+        self.push_kind(video(from_path(path.as_ref(), limit)?));
+        Ok(())
+    }
+
+    /// 读取短视频，以 base64 发送
+    pub fn push_video_reader(&mut self, reader: impl Read) -> Result<(), MessageError> {
+        self.push_video_reader_with_limit(reader, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// 读取短视频，以 base64 发送，使用 `limit` 作为大小限制，单位字节，0 为不限制
+    pub fn push_video_reader_with_limit(
+        &mut self,
+        reader: impl Read,
+        limit: u64,
+    ) -> Result<(), MessageError> {
+        self.push_kind(video(from_reader(reader, limit)?));
+        Ok(())
+    }
+}
+
+#[test]
+fn media_segments() {
+    use serde_json::json;
+
+    let msg = Message::new().add_image_bytes(b"kovi").unwrap();
+    assert_eq!(msg[0].data, json!({ "file": "base64://a292aQ==" }));
+
+    let msg = Message::new().add_record_reader(&b"kovi"[..]).unwrap();
+    assert_eq!(msg[0].data, json!({ "file": "base64://a292aQ==" }));
+
+    let path = std::env::temp_dir().join("kovi_media_segments_test.png");
+    std::fs::write(&path, b"kovi").unwrap();
+    let msg = Message::new().add_image_path(&path).unwrap();
+    let file = msg[0].data["file"].as_str().unwrap().to_string();
+    std::fs::remove_file(&path).unwrap();
+    assert!(file.starts_with("file:///") && file.ends_with("kovi_media_segments_test.png"));
+
+    // 文件名中的空格与 `#` 需要编码
+    let path = std::env::temp_dir().join("kovi media #1.png");
+    std::fs::write(&path, b"kovi").unwrap();
+    let msg = Message::new().add_image_path(&path).unwrap();
+    let file = msg[0].data["file"].as_str().unwrap().to_string();
+    std::fs::remove_file(&path).unwrap();
+    assert!(file.starts_with("file:///") && file.ends_with("/kovi%20media%20%231.png"));
+    #[cfg(unix)]
+    assert_eq!(
+        file_uri(Path::new("/tmp/a b/c#d%e.png")),
+        "file:///tmp/a%20b/c%23d%25e.png"
+    );
+
+    let r = Message::new().add_video_path("/this/file/does/not/exist.mp4");
+    assert!(matches!(r, Err(MessageError::MediaReadError(_))));
+
+    // 默认的大小限制
+    let big = vec![0u8; (DEFAULT_SIZE_LIMIT + 1) as usize];
+    assert!(matches!(
+        Message::new().add_image_reader(&big[..]),
+        Err(MessageError::MediaTooLarge { .. })
+    ));
+
+    // 指定的大小限制
+    assert!(matches!(
+        Message::new().add_image_bytes_with_limit(b"kovi", 3),
+        Err(MessageError::MediaTooLarge { size: 4, limit: 3 })
+    ));
+    assert!(matches!(
+        Message::new().add_image_reader_with_limit(&b"kovi"[..], 3),
+        Err(MessageError::MediaTooLarge { size: 4, limit: 3 })
+    ));
+    assert!(Message::new().add_video_bytes_with_limit(&big, 0).is_ok());
+}
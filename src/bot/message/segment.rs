@@ -0,0 +1,699 @@
+//! OneBot v11 消息段的类型。
+//!
+//! [`Segment`] 不检查 `data` 的格式，[`SegmentKind`] 按照 OneBot v11 标准解析并检查每种消息段。
+//! 标准之外的消息段类型解析为 [`SegmentKind::Unknown`]，原样保留。
+//! 标准消息段中，标准之外的字段不会保留，需要时请直接使用 [`Segment`]。
+
+use super::{Message, Segment};
+use crate::error::MessageError;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// 按照 OneBot v11 标准解析的消息段
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Segment", into = "Segment")]
+pub enum SegmentKind {
+    /// 纯文本
+    Text(TextData),
+    /// QQ 表情
+    Face(FaceData),
+    /// 图片
+    Image(ImageData),
+    /// 语音
+    Record(RecordData),
+    /// 短视频
+    Video(VideoData),
+    /// @某人
+    At(AtData),
+    /// 猜拳魔法表情
+    Rps,
+    /// 掷骰子魔法表情
+    Dice,
+    /// 窗口抖动（戳一戳），只能发送
+    Shake,
+    /// 戳一戳
+    Poke(PokeData),
+    /// 匿名发消息，只能发送
+    Anonymous(AnonymousData),
+    /// 链接分享
+    Share(ShareData),
+    /// 推荐好友或群
+    Contact(ContactData),
+    /// 位置
+    Location(LocationData),
+    /// 音乐分享，只能发送
+    Music(MusicData),
+    /// 回复
+    Reply(ReplyData),
+    /// 合并转发，只能接收
+    Forward(ForwardData),
+    /// 合并转发节点，只能发送
+    Node(NodeData),
+    /// XML 消息
+    Xml(XmlData),
+    /// JSON 消息
+    Json(JsonData),
+    /// 标准之外的消息段，原样保留
+    Unknown(Segment),
+}
+
+/// 纯文本
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextData {
+    pub text: String,
+}
+
+/// QQ 表情
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FaceData {
+    /// 表情 ID
+    #[serde(with = "as_str")]
+    pub id: i32,
+}
+
+/// 图片
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ImageData {
+    /// 图片文件名，发送时可以是 `file://`、`http://`、`base64://` 开头的地址
+    pub file: String,
+    /// 图片类型，`flash` 表示闪照
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    /// 图片 URL，只在接收时有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// 网络文件是否使用已缓存的文件
+    #[serde(default, with = "opt_bool", skip_serializing_if = "Option::is_none")]
+    pub cache: Option<bool>,
+    /// 网络文件是否通过代理下载
+    #[serde(default, with = "opt_bool", skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<bool>,
+    /// 下载网络文件的超时时间，单位秒
+    #[serde(default, with = "opt_as_str", skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+/// 语音
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RecordData {
+    /// 语音文件名，发送时与图片相同
+    pub file: String,
+    /// 是否变声
+    #[serde(default, with = "opt_bool", skip_serializing_if = "Option::is_none")]
+    pub magic: Option<bool>,
+    /// 语音 URL，只在接收时有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, with = "opt_bool", skip_serializing_if = "Option::is_none")]
+    pub cache: Option<bool>,
+    #[serde(default, with = "opt_bool", skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<bool>,
+    #[serde(default, with = "opt_as_str", skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+/// 短视频
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct VideoData {
+    /// 视频文件名，发送时与图片相同
+    pub file: String,
+    /// 视频 URL，只在接收时有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, with = "opt_bool", skip_serializing_if = "Option::is_none")]
+    pub cache: Option<bool>,
+    #[serde(default, with = "opt_bool", skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<bool>,
+    #[serde(default, with = "opt_as_str", skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+/// @某人
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AtData {
+    #[serde(with = "as_str")]
+    pub qq: AtTarget,
+}
+
+/// @ 的对象
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtTarget {
+    /// 全体成员
+    All,
+    User(i64),
+}
+
+impl Display for AtTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AtTarget::All => f.write_str("all"),
+            AtTarget::User(id) => write!(f, "{id}"),
+        }
+    }
+}
+
+impl FromStr for AtTarget {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(AtTarget::All),
+            _ => s.parse().map(AtTarget::User),
+        }
+    }
+}
+
+/// 戳一戳
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PokeData {
+    /// 类型，见 [Mirai 的 PokeMessage 类](https://github.com/mamoe/mirai/blob/f5eefae7ecee84d18a66afce3f89b89fe1584b78/mirai-core/src/commonMain/kotlin/net.mamoe.mirai/message/data/HummerMessage.kt#L49)
+    #[serde(rename = "type", with = "as_str")]
+    pub type_: i32,
+    /// ID
+    #[serde(with = "as_str")]
+    pub id: i32,
+    /// 表情名，只在接收时有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// 匿名发消息
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct AnonymousData {
+    /// 无法匿名时是否继续发送
+    #[serde(default, with = "opt_bool", skip_serializing_if = "Option::is_none")]
+    pub ignore: Option<bool>,
+}
+
+/// 链接分享
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ShareData {
+    pub url: String,
+    pub title: String,
+    /// 内容描述
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// 图片 URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+/// 推荐好友或群
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContactData {
+    #[serde(rename = "type")]
+    pub type_: ContactType,
+    /// 被推荐的 QQ 号或群号
+    #[serde(with = "as_str")]
+    pub id: i64,
+}
+
+/// 推荐的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContactType {
+    /// 好友
+    Qq,
+    /// 群
+    Group,
+}
+
+/// 位置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocationData {
+    /// 纬度
+    #[serde(with = "as_str")]
+    pub lat: f64,
+    /// 经度
+    #[serde(with = "as_str")]
+    pub lon: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// 内容描述
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
+/// 音乐分享
+///
+/// `type` 为 `qq`、`163`、`xm` 时需要 `id`，为 `custom` 时需要 `url`、`audio`、`title`
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct MusicData {
+    /// `qq`、`163`、`xm` 分别为 QQ 音乐、网易云音乐、虾米音乐，`custom` 为自定义
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// 歌曲 ID
+    #[serde(default, with = "opt_as_str", skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// 点击后跳转的 URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// 音乐 URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// 内容描述
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// 图片 URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+impl MusicData {
+    fn validate(&self) -> Result<(), String> {
+        if self.type_ == "custom" {
+            if self.url.is_none() || self.audio.is_none() || self.title.is_none() {
+                return Err("custom music requires url, audio and title".to_string());
+            }
+        } else if self.id.is_none() {
+            return Err(format!("{} music requires id", self.type_));
+        }
+        Ok(())
+    }
+}
+
+/// 音乐平台
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MusicPlatform {
+    /// QQ 音乐
+    Qq,
+    /// 网易云音乐
+    NetEase,
+    /// 虾米音乐
+    Xiami,
+}
+
+impl MusicPlatform {
+    /// 音乐分享消息段中的 `type`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MusicPlatform::Qq => "qq",
+            MusicPlatform::NetEase => "163",
+            MusicPlatform::Xiami => "xm",
+        }
+    }
+}
+
+/// 回复
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplyData {
+    /// 回复的消息 ID
+    #[serde(with = "as_str")]
+    pub id: i32,
+}
+
+/// 合并转发
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForwardData {
+    /// 合并转发 ID，需通过 `get_forward_msg` 获取具体内容
+    #[serde(with = "as_str")]
+    pub id: String,
+}
+
+/// 合并转发节点
+///
+/// 有 `id` 时转发已有的消息，否则为自定义节点，需要 `content`
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct NodeData {
+    /// 转发的消息 ID
+    #[serde(default, with = "opt_as_str", skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// 自定义节点的发送者 QQ 号
+    #[serde(
+        default,
+        alias = "uin",
+        with = "opt_as_str",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub user_id: Option<i64>,
+    /// 自定义节点的发送者昵称
+    #[serde(default, alias = "name", skip_serializing_if = "Option::is_none")]
+    pub nickname: Option<String>,
+    /// 自定义节点的消息内容
+    #[serde(
+        default,
+        deserialize_with = "node_content",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub content: Option<Message>,
+}
+
+impl NodeData {
+    fn validate(&self) -> Result<(), String> {
+        if self.id.is_none() && self.content.is_none() {
+            return Err("node requires id or content".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// 节点的内容可以是消息段数组，也可以是字符串
+fn node_content<'de, D>(d: D) -> Result<Option<Message>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match Option::<Value>::deserialize(d)? {
+        None => Ok(None),
+        Some(v) => Message::from_value(v)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+/// XML 消息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct XmlData {
+    /// XML 内容
+    pub data: String,
+}
+
+/// JSON 消息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonData {
+    /// JSON 内容
+    pub data: String,
+}
+
+impl SegmentKind {
+    /// 消息段的类型，如 `text`、`image`
+    pub fn type_name(&self) -> &str {
+        match self {
+            SegmentKind::Text(_) => "text",
+            SegmentKind::Face(_) => "face",
+            SegmentKind::Image(_) => "image",
+            SegmentKind::Record(_) => "record",
+            SegmentKind::Video(_) => "video",
+            SegmentKind::At(_) => "at",
+            SegmentKind::Rps => "rps",
+            SegmentKind::Dice => "dice",
+            SegmentKind::Shake => "shake",
+            SegmentKind::Poke(_) => "poke",
+            SegmentKind::Anonymous(_) => "anonymous",
+            SegmentKind::Share(_) => "share",
+            SegmentKind::Contact(_) => "contact",
+            SegmentKind::Location(_) => "location",
+            SegmentKind::Music(_) => "music",
+            SegmentKind::Reply(_) => "reply",
+            SegmentKind::Forward(_) => "forward",
+            SegmentKind::Node(_) => "node",
+            SegmentKind::Xml(_) => "xml",
+            SegmentKind::Json(_) => "json",
+            SegmentKind::Unknown(segment) => &segment.type_,
+        }
+    }
+}
+
+fn parse_data<T>(type_: &str, data: Value) -> Result<T, MessageError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    serde_json::from_value(data)
+        .map_err(|e| MessageError::ParseError(format!("Invalid {type_} segment: {e}")))
+}
+
+impl TryFrom<Segment> for SegmentKind {
+    type Error = MessageError;
+
+    fn try_from(segment: Segment) -> Result<Self, Self::Error> {
+        let Segment { type_, data } = segment;
+        let t = type_.as_str();
+        let kind = match t {
+            "text" => SegmentKind::Text(parse_data(t, data)?),
+            "face" => SegmentKind::Face(parse_data(t, data)?),
+            "image" => SegmentKind::Image(parse_data(t, data)?),
+            "record" => SegmentKind::Record(parse_data(t, data)?),
+            "video" => SegmentKind::Video(parse_data(t, data)?),
+            "at" => SegmentKind::At(parse_data(t, data)?),
+            "rps" => SegmentKind::Rps,
+            "dice" => SegmentKind::Dice,
+            "shake" => SegmentKind::Shake,
+            "poke" => SegmentKind::Poke(parse_data(t, data)?),
+            "anonymous" => SegmentKind::Anonymous(parse_data(t, data)?),
+            "share" => SegmentKind::Share(parse_data(t, data)?),
+            "contact" => SegmentKind::Contact(parse_data(t, data)?),
+            "location" => SegmentKind::Location(parse_data(t, data)?),
+            "music" => {
+                let music: MusicData = parse_data(t, data)?;
+                music
+                    .validate()
+                    .map_err(|e| MessageError::ParseError(format!("Invalid music segment: {e}")))?;
+                SegmentKind::Music(music)
+            }
+            "reply" => SegmentKind::Reply(parse_data(t, data)?),
+            "forward" => SegmentKind::Forward(parse_data(t, data)?),
+            "node" => {
+                let node: NodeData = parse_data(t, data)?;
+                node.validate()
+                    .map_err(|e| MessageError::ParseError(format!("Invalid node segment: {e}")))?;
+                SegmentKind::Node(node)
+            }
+            "xml" => SegmentKind::Xml(parse_data(t, data)?),
+            "json" => SegmentKind::Json(parse_data(t, data)?),
+            _ => SegmentKind::Unknown(Segment { type_, data }),
+        };
+        Ok(kind)
+    }
+}
+
+impl From<SegmentKind> for Segment {
+    fn from(kind: SegmentKind) -> Self {
+        fn data<T: Serialize>(v: T) -> Value {
+            // 数据结构都可以序列化为 JSON 对象
+            serde_json::to_value(v).unwrap_or_else(|_| json!({}))
+        }
+
+        let type_ = kind.type_name().to_string();
+        let data = match kind {
+            SegmentKind::Text(v) => data(v),
+            SegmentKind::Face(v) => data(v),
+            SegmentKind::Image(v) => data(v),
+            SegmentKind::Record(v) => data(v),
+            SegmentKind::Video(v) => data(v),
+            SegmentKind::At(v) => data(v),
+            SegmentKind::Rps | SegmentKind::Dice | SegmentKind::Shake => json!({}),
+            SegmentKind::Poke(v) => data(v),
+            SegmentKind::Anonymous(v) => data(v),
+            SegmentKind::Share(v) => data(v),
+            SegmentKind::Contact(v) => data(v),
+            SegmentKind::Location(v) => data(v),
+            SegmentKind::Music(v) => data(v),
+            SegmentKind::Reply(v) => data(v),
+            SegmentKind::Forward(v) => data(v),
+            SegmentKind::Node(v) => data(v),
+            SegmentKind::Xml(v) => data(v),
+            SegmentKind::Json(v) => data(v),
+            SegmentKind::Unknown(segment) => return segment,
+        };
+        Segment { type_, data }
+    }
+}
+
+impl Segment {
+    /// 按照 OneBot v11 标准解析此消息段
+    ///
+    /// # Error
+    ///
+    /// 标准消息段的 data 格式不正确时返回 `MessageError::ParseError`
+    pub fn kind(&self) -> Result<SegmentKind, MessageError> {
+        SegmentKind::try_from(self.clone())
+    }
+}
+
+impl Message {
+    /// 按照 OneBot v11 标准解析所有消息段
+    ///
+    /// # Error
+    ///
+    /// 任意一个标准消息段的 data 格式不正确时返回 `MessageError::ParseError`
+    pub fn kinds(&self) -> Result<Vec<SegmentKind>, MessageError> {
+        self.iter().map(Segment::kind).collect()
+    }
+}
+
+impl From<SegmentKind> for Message {
+    fn from(v: SegmentKind) -> Self {
+        Message(vec![v.into()])
+    }
+}
+
+impl From<Vec<SegmentKind>> for Message {
+    fn from(v: Vec<SegmentKind>) -> Self {
+        v.into_iter().collect()
+    }
+}
+
+impl FromIterator<SegmentKind> for Message {
+    fn from_iter<I: IntoIterator<Item = SegmentKind>>(iter: I) -> Self {
+        Message(iter.into_iter().map(Segment::from).collect())
+    }
+}
+
+impl TryFrom<Message> for Vec<SegmentKind> {
+    type Error = MessageError;
+
+    fn try_from(v: Message) -> Result<Self, Self::Error> {
+        v.into_iter().map(SegmentKind::try_from).collect()
+    }
+}
+
+/// OneBot v11 的 data 中的值都是字符串，部分实现会使用数字。两种都接受，序列化为字符串
+mod as_str {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use serde_json::Value;
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    pub(super) fn serialize<T, S>(v: &T, s: S) -> Result<S::Ok, S::Error>
+    where
+        T: Display,
+        S: Serializer,
+    {
+        s.collect_str(v)
+    }
+
+    pub(super) fn deserialize<'de, T, D>(d: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        parse(Value::deserialize(d)?)
+    }
+
+    pub(super) fn parse<T, E>(v: Value) -> Result<T, E>
+    where
+        T: FromStr,
+        T::Err: Display,
+        E: de::Error,
+    {
+        match v {
+            Value::String(v) => v.parse().map_err(E::custom),
+            Value::Number(v) => v.to_string().parse().map_err(E::custom),
+            v => Err(E::custom(format!("expected string or number, found {v}"))),
+        }
+    }
+}
+
+mod opt_as_str {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde_json::Value;
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    pub(super) fn serialize<T, S>(v: &Option<T>, s: S) -> Result<S::Ok, S::Error>
+    where
+        T: Display,
+        S: Serializer,
+    {
+        match v {
+            Some(v) => s.collect_str(v),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub(super) fn deserialize<'de, T, D>(d: D) -> Result<Option<T>, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        match Option::<Value>::deserialize(d)? {
+            None | Some(Value::Null) => Ok(None),
+            Some(v) => super::as_str::parse(v).map(Some),
+        }
+    }
+}
+
+/// 布尔值序列化为 `"1"` 与 `"0"`，接受布尔值、`0`/`1`、`"true"`/`"false"`
+mod opt_bool {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use serde_json::Value;
+
+    pub(super) fn serialize<S>(v: &Option<bool>, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match v {
+            Some(v) => s.serialize_str(if *v { "1" } else { "0" }),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub(super) fn deserialize<'de, D>(d: D) -> Result<Option<bool>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let v = match Option::<Value>::deserialize(d)? {
+            None | Some(Value::Null) => return Ok(None),
+            Some(v) => v,
+        };
+        let b = match &v {
+            Value::Bool(v) => *v,
+            Value::Number(n) if n.as_u64() == Some(1) => true,
+            Value::Number(n) if n.as_u64() == Some(0) => false,
+            Value::String(s) if s == "1" || s == "true" => true,
+            Value::String(s) if s == "0" || s == "false" => false,
+            _ => return Err(de::Error::custom(format!("expected bool, found {v}"))),
+        };
+        Ok(Some(b))
+    }
+}
+
+#[test]
+fn segment_kind() {
+    let msg = Message::from_value(json!([
+        { "type": "reply", "data": { "id": "123" } },
+        { "type": "at", "data": { "qq": 10000 } },
+        { "type": "at", "data": { "qq": "all" } },
+        { "type": "image", "data": { "file": "a.jpg", "type": "flash", "cache": "0" } },
+        { "type": "location", "data": { "lat": "39.8969", "lon": "116.3109" } },
+        { "type": "node", "data": { "user_id": "10001", "nickname": "某人", "content": "hi" } },
+        { "type": "dice", "data": {} },
+        { "type": "mface", "data": { "emoji_id": "1" } },
+    ]))
+    .unwrap();
+
+    let kinds = msg.kinds().unwrap();
+    assert_eq!(kinds[0], SegmentKind::Reply(ReplyData { id: 123 }));
+    assert_eq!(
+        kinds[1],
+        SegmentKind::At(AtData {
+            qq: AtTarget::User(10000)
+        })
+    );
+    assert_eq!(kinds[2], SegmentKind::At(AtData { qq: AtTarget::All }));
+    assert_eq!(
+        kinds[3],
+        SegmentKind::Image(ImageData {
+            file: "a.jpg".to_string(),
+            type_: Some("flash".to_string()),
+            cache: Some(false),
+            ..Default::default()
+        })
+    );
+    assert!(
+        matches!(&kinds[5], SegmentKind::Node(node) if node.content == Some(Message::from("hi")))
+    );
+    assert_eq!(kinds[6], SegmentKind::Dice);
+    assert_eq!(kinds[7].type_name(), "mface");
+
+    // 转换回 Message 时使用标准的字符串格式，标准之外的消息段原样保留
+    let back = Message::from(kinds);
+    assert_eq!(back[1].data, json!({ "qq": "10000" }));
+    assert_eq!(
+        back[3].data,
+        json!({ "file": "a.jpg", "type": "flash", "cache": "0" })
+    );
+    assert_eq!(back[7], msg[7]);
+    assert_eq!(back.kinds().unwrap(), msg.kinds().unwrap());
+
+    // 标准消息段的格式不正确
+    let bad = Segment::new("face", json!({ "id": "smile" }));
+    assert!(bad.kind().is_err());
+    let bad = Segment::new("music", json!({ "type": "custom", "url": "https://a" }));
+    assert!(bad.kind().is_err());
+}
@@ -1,13 +1,13 @@
+use super::{queue, ApiAndOneshot, Host, RetryPolicy, PLUGIN_BUILDER, PLUGIN_NAME};
 use super::{runtimebot::RuntimeBot, Bot};
-use super::{ApiAndOneshot, Host, PLUGIN_BUILDER, PLUGIN_NAME};
 use croner::errors::CronError;
 use croner::Cron;
-use event::{MsgEvent, NoticeEvent, RequestEvent};
+use event::{HeartbeatEvent, LifecycleEvent, MsgEvent, NoticeEvent, NoticeType, RequestEvent};
 use log::error;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::{Arc, RwLock};
-use tokio::sync::mpsc;
+use std::time::Duration;
 
 pub mod event;
 
@@ -21,6 +21,13 @@ pub type AllRequestFn = Arc<dyn Fn(Arc<RequestEvent>) -> PinFut + Send + Sync>;
 
 pub type NoArgsFn = Arc<dyn Fn() -> PinFut + Send + Sync>;
 
+pub type LifecycleFn = Arc<dyn Fn(Arc<LifecycleEvent>) -> PinFut + Send + Sync>;
+
+pub type HeartbeatFn = Arc<dyn Fn(Arc<HeartbeatEvent>) -> PinFut + Send + Sync>;
+
+/// 参数为账号的 self_id，未知时为 0
+pub type SelfIdFn = Arc<dyn Fn(i64) -> PinFut + Send + Sync>;
+
 #[derive(Clone, Default)]
 pub(crate) struct Listen {
     pub(crate) msg: Vec<Arc<ListenMsgFn>>,
@@ -29,6 +36,10 @@ pub(crate) struct Listen {
     pub(crate) notice: Vec<AllNoticeFn>,
     pub(crate) request: Vec<AllRequestFn>,
     pub(crate) drop: Vec<NoArgsFn>,
+    pub(crate) connected: Vec<LifecycleFn>,
+    pub(crate) disconnected: Vec<SelfIdFn>,
+    pub(crate) heartbeat: Vec<HeartbeatFn>,
+    pub(crate) heartbeat_timeout: Vec<SelfIdFn>,
 }
 
 #[derive(Clone)]
@@ -45,10 +56,18 @@ impl Listen {
         self.notice.clear();
         self.request.clear();
         self.drop.clear();
+        self.connected.clear();
+        self.disconnected.clear();
+        self.heartbeat.clear();
+        self.heartbeat_timeout.clear();
         self.msg.shrink_to_fit();
         self.notice.shrink_to_fit();
         self.request.shrink_to_fit();
         self.drop.shrink_to_fit();
+        self.connected.shrink_to_fit();
+        self.disconnected.shrink_to_fit();
+        self.heartbeat.shrink_to_fit();
+        self.heartbeat_timeout.shrink_to_fit();
         #[cfg(feature = "message_sent")]
         self.msg_sent.clear();
         #[cfg(feature = "message_sent")]
@@ -68,7 +87,9 @@ impl PluginBuilder {
         bot: Arc<RwLock<Bot>>,
         host: Host,
         port: u16,
-        api_tx: mpsc::Sender<ApiAndOneshot>,
+        api_tx: queue::Sender<ApiAndOneshot>,
+        api_timeout: Option<Duration>,
+        retry: Arc<RetryPolicy>,
     ) -> Self {
         let bot_weak = Arc::downgrade(&bot);
 
@@ -79,6 +100,8 @@ impl PluginBuilder {
             bot: bot_weak,
             plugin_name: name,
             api_tx,
+            api_timeout,
+            retry,
         });
 
         PluginBuilder { bot, runtime_bot }
@@ -125,6 +148,7 @@ impl PluginBuilder {
             }));
 
             bot_plugin.listen.msg.push(Arc::new(listen_fn));
+            bot.publish_listen();
         })
     }
 
@@ -155,6 +179,7 @@ impl PluginBuilder {
                         })
                     }
                 }))));
+            bot.publish_listen();
         })
     }
 
@@ -185,6 +210,7 @@ impl PluginBuilder {
                         })
                     }
                 }))));
+            bot.publish_listen();
         })
     }
 
@@ -212,6 +238,7 @@ impl PluginBuilder {
                         })
                     }
                 }))));
+            bot.publish_listen();
         })
     }
 
@@ -238,6 +265,7 @@ impl PluginBuilder {
                     })
                 }
             }));
+            bot.publish_listen();
         })
     }
 
@@ -265,6 +293,45 @@ impl PluginBuilder {
                     })
                 }
             }));
+            bot.publish_listen();
+        })
+    }
+
+    /// 注册指定类型的通知处理函数。
+    ///
+    /// 只有通知类型为 `T` 时才会调用处理程序，`T` 可以是 `notice_event` 中实现了 `NoticeType` 的类型。
+    ///
+    /// # Examples
+    /// ```ignore
+    /// PluginBuilder::on_notice(|e: Arc<GroupIncrease>| async move {
+    ///     bot.send_group_msg(e.group_id, "欢迎新成员");
+    /// });
+    /// ```
+    pub fn on_notice<T, F, Fut>(handler: F)
+    where
+        T: NoticeType,
+        F: Fn(Arc<T>) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: Send,
+    {
+        PLUGIN_BUILDER.with(|p| {
+            let mut bot = p.bot.write().unwrap();
+            let bot_plugin = bot.plugins.get_mut(&p.runtime_bot.plugin_name).unwrap();
+
+            bot_plugin.listen.notice.push(Arc::new({
+                let handler = Arc::new(handler);
+                move |event: Arc<NoticeEvent>| {
+                    Box::pin({
+                        let handler = handler.clone();
+                        async move {
+                            if let Some(notice) = T::from_notice(&event.notice) {
+                                handler(Arc::new(notice.clone())).await;
+                            }
+                        }
+                    })
+                }
+            }));
+            bot.publish_listen();
         })
     }
 
@@ -292,6 +359,119 @@ impl PluginBuilder {
                     })
                 }
             }));
+            bot.publish_listen();
+        })
+    }
+
+    /// 注册连接成功事件处理函数。
+    ///
+    /// OneBot 服务端上报 `lifecycle` 元事件（`connect` 或 `enable`）时调用，断线重连后也会再次调用。
+    pub fn on_connected<F, Fut>(handler: F)
+    where
+        F: Fn(Arc<LifecycleEvent>) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: Send,
+    {
+        PLUGIN_BUILDER.with(|p| {
+            let mut bot = p.bot.write().unwrap();
+            let bot_plugin = bot.plugins.get_mut(&p.runtime_bot.plugin_name).unwrap();
+
+            bot_plugin.listen.connected.push(Arc::new({
+                let handler = Arc::new(handler);
+                move |event| {
+                    Box::pin({
+                        let handler = handler.clone();
+                        async move {
+                            handler(event).await;
+                        }
+                    })
+                }
+            }));
+            bot.publish_listen();
+        })
+    }
+
+    /// 注册连接断开事件处理函数。
+    ///
+    /// 与 OneBot 服务端的事件连接断开，或服务端上报 `lifecycle` 的 `disable` 时调用，参数为账号的 self_id。
+    /// 连接恢复后会触发 `on_connected`。
+    pub fn on_disconnected<F, Fut>(handler: F)
+    where
+        F: Fn(i64) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: Send,
+    {
+        PLUGIN_BUILDER.with(|p| {
+            let mut bot = p.bot.write().unwrap();
+            let bot_plugin = bot.plugins.get_mut(&p.runtime_bot.plugin_name).unwrap();
+
+            bot_plugin.listen.disconnected.push(Arc::new({
+                let handler = Arc::new(handler);
+                move |self_id| {
+                    Box::pin({
+                        let handler = handler.clone();
+                        async move {
+                            handler(self_id).await;
+                        }
+                    })
+                }
+            }));
+            bot.publish_listen();
+        })
+    }
+
+    /// 注册心跳事件处理函数。
+    pub fn on_heartbeat<F, Fut>(handler: F)
+    where
+        F: Fn(Arc<HeartbeatEvent>) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: Send,
+    {
+        PLUGIN_BUILDER.with(|p| {
+            let mut bot = p.bot.write().unwrap();
+            let bot_plugin = bot.plugins.get_mut(&p.runtime_bot.plugin_name).unwrap();
+
+            bot_plugin.listen.heartbeat.push(Arc::new({
+                let handler = Arc::new(handler);
+                move |event| {
+                    Box::pin({
+                        let handler = handler.clone();
+                        async move {
+                            handler(event).await;
+                        }
+                    })
+                }
+            }));
+            bot.publish_listen();
+        })
+    }
+
+    /// 注册心跳超时事件处理函数。
+    ///
+    /// 超过心跳的 `interval` 一半时间仍未收到下一次心跳时调用，参数为账号的 self_id。
+    /// 只会在收到过心跳后开始检测，每次超时只调用一次。
+    pub fn on_heartbeat_timeout<F, Fut>(handler: F)
+    where
+        F: Fn(i64) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send,
+        Fut::Output: Send,
+    {
+        PLUGIN_BUILDER.with(|p| {
+            let mut bot = p.bot.write().unwrap();
+            let bot_plugin = bot.plugins.get_mut(&p.runtime_bot.plugin_name).unwrap();
+
+            bot_plugin.listen.heartbeat_timeout.push(Arc::new({
+                let handler = Arc::new(handler);
+                move |self_id| {
+                    Box::pin({
+                        let handler = handler.clone();
+                        async move {
+                            handler(self_id).await;
+                        }
+                    })
+                }
+            }));
+            bot.publish_listen();
         })
     }
 
@@ -319,6 +499,7 @@ impl PluginBuilder {
                     })
                 }
             }));
+            bot.publish_listen();
         })
     }
 
@@ -438,14 +619,13 @@ macro_rules! async_move {
 #[cfg(test)]
 mod on_is_ture {
     use crate::{
-        bot::{plugin_builder::ListenMsgFn, ApiAndOneshot, PLUGIN_BUILDER},
+        bot::{plugin_builder::ListenMsgFn, queue, ApiAndOneshot, PLUGIN_BUILDER},
         Bot, PluginBuilder,
     };
     use std::{
         net::{IpAddr, Ipv4Addr},
         sync::{Arc, RwLock},
     };
-    use tokio::sync::mpsc;
 
     #[tokio::test]
     async fn on_is_ture() {
@@ -461,8 +641,8 @@ mod on_is_ture {
             false,
         );
 
-        let (api_tx, _): (mpsc::Sender<ApiAndOneshot>, mpsc::Receiver<ApiAndOneshot>) =
-            mpsc::channel(1);
+        let (api_tx, _): (queue::Sender<ApiAndOneshot>, queue::Receiver<ApiAndOneshot>) =
+            queue::channel("api", &Default::default());
 
         async fn test_something() {
             PluginBuilder::on_msg(|_| async {});
@@ -491,6 +671,8 @@ mod on_is_ture {
             crate::bot::Host::IpAddr(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1))),
             8081,
             api_tx,
+            None,
+            Default::default(),
         );
         PLUGIN_BUILDER.scope(p, (main_foo)()).await;
 
@@ -1,8 +1,18 @@
-pub use msg_event::MsgEvent;
-use serde::{Deserialize, Serialize};
+pub use meta_event::{HeartbeatEvent, LifecycleEvent};
+pub use msg_event::{MsgEvent, MsgQuickOperation};
+pub use notice_event::{Notice, NoticeEvent, NoticeType, Notify};
+use parking_lot::Mutex;
+pub use request_event::{FriendRequest, GroupRequest, GroupRequestType, Request, RequestEvent};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::{self, Value};
+use std::sync::Arc;
+use tokio::sync::oneshot;
 
+pub mod meta_event;
 pub mod msg_event;
+pub mod notice_event;
+pub mod request_event;
 
 #[deprecated(since = "0.11.0", note = "请使用 `NoticeEvent` 代替")]
 pub type AllNoticeEvent = NoticeEvent;
@@ -10,24 +20,58 @@ pub type AllNoticeEvent = NoticeEvent;
 #[deprecated(since = "0.11.0", note = "请使用 `RequestEvent` 代替")]
 pub type AllRequestEvent = RequestEvent;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Sex {
     Male,
     Female,
 }
 
-#[derive(Debug, Clone)]
+/// 发送人信息，各实现返回的字段不同，缺少或无法解析的字段为 `None`
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct Sender {
+    #[serde(default)]
     pub user_id: i64,
+    #[serde(default, deserialize_with = "lenient")]
     pub nickname: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
     pub card: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
     pub sex: Option<Sex>,
+    #[serde(default, deserialize_with = "lenient")]
     pub age: Option<i32>,
+    #[serde(default, deserialize_with = "lenient")]
     pub area: Option<String>,
+    /// 部分实现返回数字，统一转换为字符串
+    #[serde(default, deserialize_with = "lenient_string")]
     pub level: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
     pub role: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
     pub title: Option<String>,
 }
+
+/// 反序列化可选字段，为 null 或类型不符时为 `None`
+pub(crate) fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let v = Value::deserialize(deserializer)?;
+    Ok(T::deserialize(v).ok())
+}
+
+/// 与 `lenient` 相同，但数字会转换为字符串
+pub(crate) fn lenient_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(s) => Some(s),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    })
+}
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Anonymous {
     pub id: i64,
@@ -35,64 +79,27 @@ pub struct Anonymous {
     pub flag: String,
 }
 
-#[derive(Debug, Clone)]
-pub struct NoticeEvent {
-    /// 事件发生的时间戳
-    pub time: i64,
-    /// 收到事件的机器人 登陆号
-    pub self_id: i64,
-    /// 上报类型
-    pub post_type: String,
-    /// 通知类型
-    pub notice_type: String,
+/// HTTP POST 上报的快速操作通道。
+///
+/// 只有通过 HTTP POST 上报的事件才有，并且只能使用一次。
+/// 所有持有此事件的处理函数结束后通道关闭，Kovi 会以空响应回复 OneBot 服务端。
+#[derive(Debug, Clone, Default)]
+pub(crate) struct QuickOperation(Option<Arc<Mutex<Option<oneshot::Sender<Value>>>>>);
 
-    /// 原始的onebot消息，已处理成json格式
-    pub original_json: Value,
-}
-impl NoticeEvent {
-    pub(crate) fn new(msg: &str) -> Result<NoticeEvent, Box<dyn std::error::Error>> {
-        let temp: Value = serde_json::from_str(msg)?;
-        let time = temp.get("time").unwrap().as_i64().unwrap();
-        let self_id = temp.get("self_id").unwrap().as_i64().unwrap();
-        let post_type = temp.get("post_type").unwrap().to_string();
-        let notice_type = temp.get("notice_type").unwrap().to_string();
-        Ok(NoticeEvent {
-            time,
-            self_id,
-            post_type,
-            notice_type,
-            original_json: temp,
-        })
+impl QuickOperation {
+    pub(crate) fn new(tx: oneshot::Sender<Value>) -> Self {
+        QuickOperation(Some(Arc::new(Mutex::new(Some(tx)))))
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct RequestEvent {
-    /// 事件发生的时间戳
-    pub time: i64,
-    /// 收到事件的机器人 登陆号
-    pub self_id: i64,
-    /// 上报类型
-    pub post_type: String,
-    /// 请求类型
-    pub request_type: String,
-
-    /// 原始的onebot消息，已处理成json格式
-    pub original_json: Value,
-}
-impl RequestEvent {
-    pub(crate) fn new(msg: &str) -> Result<RequestEvent, Box<dyn std::error::Error>> {
-        let temp: Value = serde_json::from_str(msg)?;
-        let time = temp.get("time").unwrap().as_i64().unwrap();
-        let self_id = temp.get("self_id").unwrap().as_i64().unwrap();
-        let post_type = temp.get("post_type").unwrap().to_string();
-        let request_type = temp.get("request_type").unwrap().to_string();
-        Ok(RequestEvent {
-            time,
-            self_id,
-            post_type,
-            request_type,
-            original_json: temp,
-        })
+    /// 发送快速操作。如果此事件不支持快速操作，或者已经使用过，返回 false
+    pub(crate) fn send(&self, operation: Value) -> bool {
+        let tx = match &self.0 {
+            Some(v) => v.lock().take(),
+            None => return false,
+        };
+        match tx {
+            Some(tx) => tx.send(operation).is_ok(),
+            None => false,
+        }
     }
 }
@@ -0,0 +1,73 @@
+use serde_json::{self, Value};
+
+/// 生命周期元事件，OneBot 服务端启用、停用或连接成功时上报
+#[derive(Debug, Clone)]
+pub struct LifecycleEvent {
+    /// 事件发生的时间戳
+    pub time: i64,
+    /// 收到事件的机器人 登陆号
+    pub self_id: i64,
+    /// `enable`、`disable`、`connect`
+    pub sub_type: String,
+
+    /// 原始的onebot消息，已处理成json格式
+    pub original_json: Value,
+}
+
+impl LifecycleEvent {
+    pub(crate) fn new(json: Value) -> LifecycleEvent {
+        LifecycleEvent {
+            time: json
+                .get("time")
+                .and_then(|v| v.as_i64())
+                .unwrap_or_default(),
+            self_id: json
+                .get("self_id")
+                .and_then(|v| v.as_i64())
+                .unwrap_or_default(),
+            sub_type: json
+                .get("sub_type")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            original_json: json,
+        }
+    }
+}
+
+/// 心跳元事件
+#[derive(Debug, Clone)]
+pub struct HeartbeatEvent {
+    /// 事件发生的时间戳
+    pub time: i64,
+    /// 收到事件的机器人 登陆号
+    pub self_id: i64,
+    /// 到下次心跳的间隔，单位毫秒
+    pub interval: i64,
+    /// 状态信息，与 `get_status` 的返回值相同
+    pub status: Value,
+
+    /// 原始的onebot消息，已处理成json格式
+    pub original_json: Value,
+}
+
+impl HeartbeatEvent {
+    pub(crate) fn new(json: Value) -> HeartbeatEvent {
+        HeartbeatEvent {
+            time: json
+                .get("time")
+                .and_then(|v| v.as_i64())
+                .unwrap_or_default(),
+            self_id: json
+                .get("self_id")
+                .and_then(|v| v.as_i64())
+                .unwrap_or_default(),
+            interval: json
+                .get("interval")
+                .and_then(|v| v.as_i64())
+                .unwrap_or_default(),
+            status: json.get("status").cloned().unwrap_or_default(),
+            original_json: json,
+        }
+    }
+}
@@ -1,16 +1,13 @@
-use super::{Anonymous, Sender};
+use super::{lenient, Anonymous, QuickOperation, Sender};
 use crate::bot::runtimebot::send_api_request_with_forget;
+use crate::error::MessageError;
 use crate::{
-    bot::{plugin_builder::event::Sex, ApiAndOneshot, SendApi},
+    bot::{queue, ApiAndOneshot, SendApi},
     Message,
 };
-use log::{debug, info};
-use serde::Serialize;
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
 use serde_json::{self, json, Value};
-use tokio::sync::mpsc;
-
-#[cfg(not(feature = "cqstring"))]
-use log::error;
 
 #[cfg(feature = "cqstring")]
 use crate::bot::message::{cq_to_arr, CQMessage};
@@ -18,6 +15,30 @@ use crate::bot::message::{cq_to_arr, CQMessage};
 #[deprecated(since = "0.11.0", note = "请使用 `MsgEvent` 代替")]
 pub type AllMsgEvent = MsgEvent;
 
+/// 消息事件的原始字段，只有必需的字段缺少时才会解析失败
+#[derive(Deserialize)]
+struct RawMsgEvent {
+    time: i64,
+    self_id: i64,
+    post_type: String,
+    message_type: String,
+    #[serde(default, deserialize_with = "lenient")]
+    sub_type: Option<String>,
+    message: Value,
+    message_id: i32,
+    #[serde(default, deserialize_with = "lenient")]
+    group_id: Option<i64>,
+    user_id: i64,
+    #[serde(default, deserialize_with = "lenient")]
+    anonymous: Option<Anonymous>,
+    #[serde(default, deserialize_with = "lenient")]
+    raw_message: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    font: Option<i32>,
+    #[serde(default, deserialize_with = "lenient")]
+    sender: Option<Sender>,
+}
+
 #[derive(Debug, Clone)]
 pub struct MsgEvent {
     /// 事件发生的时间戳
@@ -54,88 +75,116 @@ pub struct MsgEvent {
     /// 原始的onebot消息，已处理成json格式
     pub original_json: Value,
 
-    api_tx: mpsc::Sender<ApiAndOneshot>,
+    pub(crate) quick_operation: QuickOperation,
+    api_tx: queue::Sender<ApiAndOneshot>,
+}
+
+/// 消息事件的快速操作，通过 `MsgEvent::quick_operation()` 使用
+///
+/// `at_sender`、`delete`、`kick`、`ban` 仅在群聊中有效
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MsgQuickOperation {
+    /// 要回复的内容
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply: Option<Message>,
+    /// 回复时是否 at 发送者，不填写时 OneBot 服务端默认为 true
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub at_sender: Option<bool>,
+    /// 撤回该条消息
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delete: Option<bool>,
+    /// 把发送者踢出群组
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kick: Option<bool>,
+    /// 把发送者禁言
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ban: Option<bool>,
+    /// 禁言时长，单位秒
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ban_duration: Option<usize>,
+}
+
+impl MsgQuickOperation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 回复消息
+    pub fn reply<T>(mut self, msg: T) -> Self
+    where
+        Message: From<T>,
+    {
+        self.reply = Some(Message::from(msg));
+        self
+    }
+
+    /// 回复时是否 at 发送者
+    pub fn at_sender(mut self, at_sender: bool) -> Self {
+        self.at_sender = Some(at_sender);
+        self
+    }
+
+    /// 撤回该条消息
+    pub fn delete(mut self) -> Self {
+        self.delete = Some(true);
+        self
+    }
+
+    /// 把发送者踢出群组
+    pub fn kick(mut self) -> Self {
+        self.kick = Some(true);
+        self
+    }
+
+    /// 把发送者禁言，`duration` 单位秒
+    pub fn ban(mut self, duration: usize) -> Self {
+        self.ban = Some(true);
+        self.ban_duration = Some(duration);
+        self
+    }
 }
 
 impl MsgEvent {
     pub(crate) fn new(
-        api_tx: mpsc::Sender<ApiAndOneshot>,
-        msg: &str,
-    ) -> Result<MsgEvent, Box<dyn std::error::Error>> {
-        let temp: Value = serde_json::from_str(msg)?;
-
-        let temp_object = temp.as_object().unwrap();
-
-        let temp_sender = temp_object["sender"].as_object().unwrap();
-
-        let sender = {
-            Sender {
-                user_id: temp_sender["user_id"].as_i64().unwrap(),
-                nickname: temp_sender
-                    .get("nickname")
-                    .map(|v| v.as_str().unwrap().to_string()),
-                card: temp_sender
-                    .get("card")
-                    .map(|v| v.as_str().unwrap().to_string()),
-                sex: if let Some(v) = temp_sender.get("sex") {
-                    match v.as_str().unwrap() {
-                        "male" => Some(Sex::Male),
-                        "female" => Some(Sex::Female),
-                        _ => None,
-                    }
-                } else {
-                    None
-                },
-                age: temp_sender.get("age").map(|v| v.as_i64().unwrap() as i32),
-                area: temp_sender
-                    .get("area")
-                    .map(|v| v.as_str().unwrap().to_string()),
-                level: temp_sender
-                    .get("level")
-                    .map(|v| v.as_str().unwrap().to_string()),
-                role: temp_sender
-                    .get("role")
-                    .map(|v| v.as_str().unwrap().to_string()),
-                title: temp_sender
-                    .get("title")
-                    .map(|v| v.as_str().unwrap().to_string()),
-            }
-        };
+        api_tx: queue::Sender<ApiAndOneshot>,
+        temp: Value,
+    ) -> Result<MsgEvent, MessageError> {
+        let raw = RawMsgEvent::deserialize(&temp)
+            .map_err(|e| MessageError::ParseError(format!("Invalid message event: {e}")))?;
+        // 群消息需要 group_id 才能回复
+        if raw.message_type == "group" && raw.group_id.is_none() {
+            return Err(MessageError::ParseError(
+                "Invalid message event: group message without a valid group_id".to_string(),
+            ));
+        }
 
-        let group_id = if let Some(v) = temp_object.get("group_id") {
-            v.as_i64()
-        } else {
-            None
-        };
-        let message = if temp_object["message"].is_array() {
-            let v = temp_object["message"].as_array().unwrap().to_vec();
-            Message::from_vec_segment_value(v).unwrap()
-        } else {
+        let message = match raw.message {
+            Value::Array(v) => Message::from_vec_segment_value(v)
+                .map_err(|e| MessageError::ParseError(e.to_string()))?,
             #[cfg(feature = "cqstring")]
-            {
-                let str = temp_object["message"].as_str().unwrap().to_string();
-                cq_to_arr(CQMessage::from(str))
-            }
+            Value::String(str) => cq_to_arr(CQMessage::from(str)),
+            // 不开启cqstring特性，不能用。
             #[cfg(not(feature = "cqstring"))]
-            {
-                // 不开启cqstring特性，不能用。
-                error!("不开启cqstring feature，不能使用cq码");
-                panic!()
+            Value::String(_) => {
+                return Err(MessageError::ParseError(
+                    "String format message requires the `cqstring` feature".to_string(),
+                ))
             }
+            v => return Err(MessageError::ParseError(format!("Invalid message: {v}"))),
         };
-        let anonymous: Option<Anonymous> =
-            if temp_object.get("anonymous").is_none() || temp_object["anonymous"].is_null() {
-                None
-            } else {
-                let anonymous = temp_object["anonymous"].clone();
-                Some(serde_json::from_value(anonymous).unwrap())
-            };
+
+        let mut sender = raw.sender.unwrap_or_default();
+        if sender.user_id == 0 {
+            sender.user_id = raw.user_id;
+        }
 
         let text = {
             let mut text_vec = Vec::new();
             for msg in message.iter() {
                 if msg.type_ == "text" {
-                    text_vec.push(msg.data.get("text").unwrap().as_str().unwrap());
+                    if let Some(text) = msg.data.get("text").and_then(|v| v.as_str()) {
+                        text_vec.push(text);
+                    }
                 };
             }
             if !text_vec.is_empty() {
@@ -147,22 +196,23 @@ impl MsgEvent {
 
         let event = MsgEvent {
             human_text: message.to_human_string(),
-            time: temp_object["time"].as_i64().unwrap(),
-            self_id: temp_object["self_id"].as_i64().unwrap(),
-            post_type: temp_object["post_type"].as_str().unwrap().to_string(),
-            message_type: temp_object["message_type"].as_str().unwrap().to_string(),
-            sub_type: temp_object["sub_type"].as_str().unwrap().to_string(),
+            time: raw.time,
+            self_id: raw.self_id,
+            post_type: raw.post_type,
+            message_type: raw.message_type,
+            sub_type: raw.sub_type.unwrap_or_default(),
             message,
-            message_id: temp_object["message_id"].as_i64().unwrap() as i32,
-            group_id,
-            user_id: temp_object["user_id"].as_i64().unwrap(),
-            anonymous,
-            raw_message: temp_object["raw_message"].as_str().unwrap().to_string(),
-            font: temp_object["font"].as_i64().unwrap() as i32,
+            message_id: raw.message_id,
+            group_id: raw.group_id,
+            user_id: raw.user_id,
+            anonymous: raw.anonymous,
+            raw_message: raw.raw_message.unwrap_or_default(),
+            font: raw.font.unwrap_or_default(),
             sender,
             api_tx,
             text,
             original_json: temp,
+            quick_operation: QuickOperation::default(),
         };
         debug!("{:?}", event);
         Ok(event)
@@ -170,12 +220,13 @@ impl MsgEvent {
 }
 
 impl MsgEvent {
-    fn reply_builder<T>(&self, msg: T, auto_escape: bool) -> SendApi
+    /// 群消息缺少 group_id 时无法回复，返回 `None`
+    fn reply_builder<T>(&self, msg: T, auto_escape: bool) -> Option<SendApi>
     where
         T: Serialize,
     {
         if self.message_type == "private" {
-            SendApi::new(
+            return Some(SendApi::new(
                 "send_msg",
                 json!({
                     "message_type":"private",
@@ -184,19 +235,22 @@ impl MsgEvent {
                 "auto_escape":auto_escape,
                 }),
                 "None",
-            )
-        } else {
-            SendApi::new(
-                "send_msg",
-                json!({
-                    "message_type":"group",
-                    "group_id":self.group_id.unwrap(),
-                    "message":msg,
-                    "auto_escape":auto_escape,
-                }),
-                "None",
-            )
+            ));
         }
+        let Some(group_id) = self.group_id else {
+            error!("Reply failed: group message without group_id");
+            return None;
+        };
+        Some(SendApi::new(
+            "send_msg",
+            json!({
+                "message_type":"group",
+                "group_id":group_id,
+                "message":msg,
+                "auto_escape":auto_escape,
+            }),
+            "None",
+        ))
     }
 
     #[cfg(not(feature = "cqstring"))]
@@ -207,7 +261,9 @@ impl MsgEvent {
         T: Serialize,
     {
         let msg = Message::from(msg);
-        let send_msg = self.reply_builder(&msg, false);
+        let Some(send_msg) = self.reply_builder(&msg, false) else {
+            return;
+        };
         let mut nickname = self.get_sender_nickname();
         nickname.insert(0, ' ');
         let id = &self.sender.user_id;
@@ -230,7 +286,9 @@ impl MsgEvent {
         T: Serialize,
     {
         let msg = CQMessage::from(msg);
-        let send_msg = self.reply_builder(&msg, false);
+        let Some(send_msg) = self.reply_builder(&msg, false) else {
+            return;
+        };
         let mut nickname = self.get_sender_nickname();
         nickname.insert(0, ' ');
         let id = &self.sender.user_id;
@@ -252,7 +310,9 @@ impl MsgEvent {
         T: Serialize,
     {
         let msg = Message::from(msg).add_reply(self.message_id);
-        let send_msg = self.reply_builder(&msg, false);
+        let Some(send_msg) = self.reply_builder(&msg, false) else {
+            return;
+        };
 
         let mut nickname = self.get_sender_nickname();
         nickname.insert(0, ' ');
@@ -276,7 +336,9 @@ impl MsgEvent {
         T: Serialize,
     {
         let msg = CQMessage::from(msg).add_reply(self.message_id);
-        let send_msg = self.reply_builder(&msg, false);
+        let Some(send_msg) = self.reply_builder(&msg, false) else {
+            return;
+        };
 
         let mut nickname = self.get_sender_nickname();
         nickname.insert(0, ' ');
@@ -298,7 +360,9 @@ impl MsgEvent {
         String: From<T>,
         T: Serialize,
     {
-        let send_msg = self.reply_builder(&msg, true);
+        let Some(send_msg) = self.reply_builder(&msg, true) else {
+            return;
+        };
         let mut nickname = self.get_sender_nickname();
         nickname.insert(0, ' ');
         let id = &self.sender.user_id;
@@ -312,6 +376,91 @@ impl MsgEvent {
         send_api_request_with_forget(&self.api_tx, send_msg);
     }
 
+    /// 使用 HTTP POST 快速操作回复消息，省去一次 `send_msg` 调用
+    ///
+    /// 只有通过 HTTP POST 上报的事件才能使用快速操作，且每个事件只能使用一次。不能使用时会退回到普通的回复。
+    pub fn quick_reply<T>(&self, msg: T)
+    where
+        Message: From<T>,
+    {
+        self.quick_operation(MsgQuickOperation::new().reply(msg).at_sender(false))
+    }
+
+    /// 使用 HTTP POST 快速操作处理消息，可以同时回复、撤回、踢出、禁言
+    ///
+    /// 只有通过 HTTP POST 上报的事件才能使用快速操作，且每个事件只能使用一次。不能使用时会退回到对应的 api 调用。
+    pub fn quick_operation(&self, operation: MsgQuickOperation) {
+        if let Some(reply) = &operation.reply {
+            let mut nickname = self.get_sender_nickname();
+            nickname.insert(0, ' ');
+            let id = &self.sender.user_id;
+            let message_type = &self.message_type;
+            let group_id = match &self.group_id {
+                Some(v) => format!(" {v}"),
+                None => "".to_string(),
+            };
+            let human_msg = reply.to_human_string();
+            info!("[reply] [to {message_type}{group_id}{nickname} {id}]: {human_msg}");
+        }
+
+        if self.quick_operation.send(json!(operation)) {
+            return;
+        }
+
+        if let Some(mut reply) = operation.reply {
+            // OneBot 中 at_sender 默认为 true
+            if operation.at_sender.unwrap_or(true) && self.is_group() {
+                reply = Message::new().add_at(&self.user_id.to_string()) + reply;
+            }
+            if let Some(send_msg) = self.reply_builder(&reply, false) {
+                send_api_request_with_forget(&self.api_tx, send_msg);
+            }
+        }
+
+        let group_id = match self.group_id {
+            Some(v) => v,
+            None => return,
+        };
+        if operation.delete == Some(true) {
+            send_api_request_with_forget(
+                &self.api_tx,
+                SendApi::new(
+                    "delete_msg",
+                    json!({ "message_id": self.message_id }),
+                    "None",
+                ),
+            );
+        }
+        if operation.kick == Some(true) {
+            send_api_request_with_forget(
+                &self.api_tx,
+                SendApi::new(
+                    "set_group_kick",
+                    json!({
+                        "group_id": group_id,
+                        "user_id": self.user_id,
+                        "reject_add_request": false,
+                    }),
+                    "None",
+                ),
+            );
+        }
+        if operation.ban == Some(true) {
+            send_api_request_with_forget(
+                &self.api_tx,
+                SendApi::new(
+                    "set_group_ban",
+                    json!({
+                        "group_id": group_id,
+                        "user_id": self.user_id,
+                        "duration": operation.ban_duration.unwrap_or(30 * 60),
+                    }),
+                    "None",
+                ),
+            );
+        }
+    }
+
     /// 便捷获取文本，如果没有文本则会返回空字符串，如果只需要借用，请使用 `borrow_text()`
     pub fn get_text(&self) -> String {
         match self.text.clone() {
@@ -342,3 +491,45 @@ impl MsgEvent {
         self.group_id.is_none()
     }
 }
+
+#[test]
+fn parse_msg_event() {
+    let (api_tx, _api_rx) = queue::channel("api", &Default::default());
+    let e = MsgEvent::new(
+        api_tx.clone(),
+        serde_json::from_str(r#"{"time":1,"self_id":2,"post_type":"message","message_type":"group","sub_type":null,"message_id":3,"group_id":4,"user_id":5,"message":[{"type":"text","data":{"text":"hi"}}],"sender":{"user_id":5,"nickname":null,"sex":"unknown","level":6}}"#).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(e.text.as_deref(), Some("hi"));
+    assert_eq!((e.sub_type.as_str(), e.font), ("", 0));
+    assert!(e.sender.nickname.is_none() && e.sender.sex.is_none());
+    assert_eq!(e.sender.level.as_deref(), Some("6"));
+
+    let e = MsgEvent::new(
+        api_tx.clone(),
+        serde_json::from_str(r#"{"time":1,"self_id":2,"post_type":"message","message_type":"private","message_id":3,"user_id":5,"message":1}"#).unwrap(),
+    );
+    assert!(matches!(e, Err(MessageError::ParseError(_))));
+
+    // 群消息的 group_id 缺少或无法解析时解析失败
+    let e = MsgEvent::new(
+        api_tx.clone(),
+        serde_json::from_str(r#"{"time":1,"self_id":2,"post_type":"message","message_type":"group","message_id":3,"group_id":"abc","user_id":5,"message":[]}"#).unwrap(),
+    );
+    assert!(matches!(e, Err(MessageError::ParseError(_))));
+
+    // 缺少 message_id 时无法回复，解析失败
+    let e = MsgEvent::new(
+        api_tx.clone(),
+        serde_json::from_str(r#"{"time":1,"self_id":2,"post_type":"message","message_type":"private","user_id":5,"message":[]}"#).unwrap(),
+    );
+    assert!(matches!(e, Err(MessageError::ParseError(_))));
+
+    let mut e = MsgEvent::new(
+        api_tx,
+        serde_json::from_str(r#"{"time":1,"self_id":2,"post_type":"message","message_type":"group","message_id":3,"group_id":4,"user_id":5,"message":[]}"#).unwrap(),
+    )
+    .unwrap();
+    e.group_id = None;
+    assert!(e.reply_builder("hi", false).is_none());
+}
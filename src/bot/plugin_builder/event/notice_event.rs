@@ -0,0 +1,336 @@
+use log::debug;
+use serde::Deserialize;
+use serde_json::{self, Value};
+
+#[derive(Debug, Clone)]
+pub struct NoticeEvent {
+    /// 事件发生的时间戳
+    pub time: i64,
+    /// 收到事件的机器人 登陆号
+    pub self_id: i64,
+    /// 上报类型
+    pub post_type: String,
+    /// 通知类型
+    pub notice_type: String,
+    /// 解析后的通知，未知或解析失败的通知为 `Notice::Unknown`
+    pub notice: Notice,
+
+    /// 原始的onebot消息，已处理成json格式
+    pub original_json: Value,
+}
+
+#[derive(Deserialize)]
+struct RawNoticeEvent {
+    time: i64,
+    self_id: i64,
+    post_type: String,
+    notice_type: String,
+}
+
+impl NoticeEvent {
+    pub(crate) fn new(temp: Value) -> Result<NoticeEvent, serde_json::Error> {
+        let RawNoticeEvent {
+            time,
+            self_id,
+            post_type,
+            notice_type,
+        } = RawNoticeEvent::deserialize(&temp)?;
+        let notice = match Notice::deserialize(&temp) {
+            Ok(v) => v,
+            Err(e) => {
+                debug!("Failed to parse notice {notice_type}: {e}");
+                Notice::Unknown
+            }
+        };
+        Ok(NoticeEvent {
+            time,
+            self_id,
+            post_type,
+            notice_type,
+            notice,
+            original_json: temp,
+        })
+    }
+}
+
+/// OneBot v11 的通知，以及 go-cqhttp 拓展的 `group_card` 与 `essence`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "notice_type", rename_all = "snake_case")]
+pub enum Notice {
+    /// 群文件上传
+    GroupUpload(GroupUpload),
+    /// 群管理员变动
+    GroupAdmin(GroupAdmin),
+    /// 群成员减少
+    GroupDecrease(GroupDecrease),
+    /// 群成员增加
+    GroupIncrease(GroupIncrease),
+    /// 群禁言
+    GroupBan(GroupBan),
+    /// 好友添加
+    FriendAdd(FriendAdd),
+    /// 群消息撤回
+    GroupRecall(GroupRecall),
+    /// 好友消息撤回
+    FriendRecall(FriendRecall),
+    /// 戳一戳、群红包运气王、群成员荣誉变更
+    Notify(Notify),
+    /// 群成员名片更新
+    GroupCard(GroupCard),
+    /// 精华消息
+    Essence(Essence),
+    /// 未知的通知
+    #[serde(other)]
+    Unknown,
+}
+
+/// `notice_type` 为 `notify` 的通知，按 `sub_type` 区分
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "sub_type", rename_all = "snake_case")]
+pub enum Notify {
+    /// 戳一戳
+    Poke(Poke),
+    /// 群红包运气王
+    LuckyKing(LuckyKing),
+    /// 群成员荣誉变更
+    Honor(Honor),
+    /// 未知的 notify
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GroupUpload {
+    pub time: i64,
+    pub self_id: i64,
+    pub group_id: i64,
+    pub user_id: i64,
+    /// 文件信息
+    pub file: UploadFile,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UploadFile {
+    /// 文件 ID
+    pub id: String,
+    /// 文件名
+    pub name: String,
+    /// 文件大小（字节数）
+    pub size: i64,
+    /// busid（目前不清楚有什么作用）
+    pub busid: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GroupAdmin {
+    pub time: i64,
+    pub self_id: i64,
+    /// `set`、`unset`，分别表示设置和取消管理员
+    pub sub_type: String,
+    pub group_id: i64,
+    /// 管理员 QQ 号
+    pub user_id: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GroupDecrease {
+    pub time: i64,
+    pub self_id: i64,
+    /// `leave`、`kick`、`kick_me`，分别表示主动退群、成员被踢、登录号被踢
+    pub sub_type: String,
+    pub group_id: i64,
+    /// 操作者 QQ 号（如果是主动退群，则和 `user_id` 相同）
+    pub operator_id: i64,
+    /// 离开者 QQ 号
+    pub user_id: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GroupIncrease {
+    pub time: i64,
+    pub self_id: i64,
+    /// `approve`、`invite`，分别表示管理员已同意入群、管理员邀请入群
+    pub sub_type: String,
+    pub group_id: i64,
+    /// 操作者 QQ 号
+    pub operator_id: i64,
+    /// 加入者 QQ 号
+    pub user_id: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GroupBan {
+    pub time: i64,
+    pub self_id: i64,
+    /// `ban`、`lift_ban`，分别表示禁言、解除禁言
+    pub sub_type: String,
+    pub group_id: i64,
+    /// 操作者 QQ 号
+    pub operator_id: i64,
+    /// 被禁言 QQ 号，全员禁言时为 0
+    pub user_id: i64,
+    /// 禁言时长，单位秒
+    pub duration: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FriendAdd {
+    pub time: i64,
+    pub self_id: i64,
+    /// 新添加好友 QQ 号
+    pub user_id: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GroupRecall {
+    pub time: i64,
+    pub self_id: i64,
+    pub group_id: i64,
+    /// 消息发送者 QQ 号
+    pub user_id: i64,
+    /// 操作者 QQ 号
+    pub operator_id: i64,
+    /// 被撤回的消息 ID
+    pub message_id: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FriendRecall {
+    pub time: i64,
+    pub self_id: i64,
+    /// 好友 QQ 号
+    pub user_id: i64,
+    /// 被撤回的消息 ID
+    pub message_id: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Poke {
+    pub time: i64,
+    pub self_id: i64,
+    /// 群号，好友戳一戳时为 `None`
+    pub group_id: Option<i64>,
+    /// 发送者 QQ 号
+    pub user_id: i64,
+    /// 被戳者 QQ 号
+    pub target_id: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LuckyKing {
+    pub time: i64,
+    pub self_id: i64,
+    pub group_id: i64,
+    /// 红包发送者 QQ 号
+    pub user_id: i64,
+    /// 运气王 QQ 号
+    pub target_id: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Honor {
+    pub time: i64,
+    pub self_id: i64,
+    pub group_id: i64,
+    /// `talkative`、`performer`、`emotion`，分别表示龙王、群聊之火、快乐源泉
+    pub honor_type: String,
+    /// 成员 QQ 号
+    pub user_id: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GroupCard {
+    pub time: i64,
+    pub self_id: i64,
+    pub group_id: i64,
+    /// 成员 QQ 号
+    pub user_id: i64,
+    /// 新名片
+    #[serde(default)]
+    pub card_new: String,
+    /// 旧名片
+    #[serde(default)]
+    pub card_old: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Essence {
+    pub time: i64,
+    pub self_id: i64,
+    /// `add`、`delete`，分别表示添加、移出精华消息
+    pub sub_type: String,
+    pub group_id: i64,
+    /// 消息发送者 QQ 号
+    pub sender_id: i64,
+    /// 操作者 QQ 号
+    pub operator_id: i64,
+    /// 消息 ID
+    pub message_id: i64,
+}
+
+/// 可以通过 `PluginBuilder::on_notice` 监听的通知类型
+pub trait NoticeType: Clone + Send + Sync + 'static {
+    /// 如果通知是此类型，返回其内容
+    fn from_notice(notice: &Notice) -> Option<&Self>;
+}
+
+macro_rules! impl_notice_type {
+    ($($ty:ident => $pat:pat => $v:ident),* $(,)?) => {
+        $(
+            impl NoticeType for $ty {
+                fn from_notice(notice: &Notice) -> Option<&Self> {
+                    match notice {
+                        $pat => Some($v),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_notice_type!(
+    GroupUpload => Notice::GroupUpload(v) => v,
+    GroupAdmin => Notice::GroupAdmin(v) => v,
+    GroupDecrease => Notice::GroupDecrease(v) => v,
+    GroupIncrease => Notice::GroupIncrease(v) => v,
+    GroupBan => Notice::GroupBan(v) => v,
+    FriendAdd => Notice::FriendAdd(v) => v,
+    GroupRecall => Notice::GroupRecall(v) => v,
+    FriendRecall => Notice::FriendRecall(v) => v,
+    Poke => Notice::Notify(Notify::Poke(v)) => v,
+    LuckyKing => Notice::Notify(Notify::LuckyKing(v)) => v,
+    Honor => Notice::Notify(Notify::Honor(v)) => v,
+    GroupCard => Notice::GroupCard(v) => v,
+    Essence => Notice::Essence(v) => v,
+);
+
+#[test]
+fn parse_notice() {
+    let e = NoticeEvent::new(serde_json::from_str(
+        r#"{"time":1,"self_id":2,"post_type":"notice","notice_type":"notify","sub_type":"poke","group_id":3,"user_id":4,"target_id":2}"#,
+    ).unwrap())
+    .unwrap();
+    assert_eq!(e.notice_type, "notify");
+    let poke = Poke::from_notice(&e.notice).unwrap();
+    assert_eq!(
+        (poke.group_id, poke.user_id, poke.target_id),
+        (Some(3), 4, 2)
+    );
+
+    let e = NoticeEvent::new(serde_json::from_str(
+        r#"{"time":1,"self_id":2,"post_type":"notice","notice_type":"group_increase","sub_type":"approve","group_id":3,"operator_id":5,"user_id":4}"#,
+    ).unwrap())
+    .unwrap();
+    assert_eq!(
+        GroupIncrease::from_notice(&e.notice).unwrap().sub_type,
+        "approve"
+    );
+    assert!(Poke::from_notice(&e.notice).is_none());
+
+    let e = NoticeEvent::new(serde_json::from_str(
+        r#"{"time":1,"self_id":2,"post_type":"notice","notice_type":"group_msg_emoji_like","group_id":3}"#,
+    ).unwrap())
+    .unwrap();
+    assert!(matches!(e.notice, Notice::Unknown));
+}
@@ -0,0 +1,326 @@
+use super::QuickOperation;
+use crate::bot::runtimebot::send_api_request_with_forget;
+use crate::bot::{queue, ApiAndOneshot, SendApi};
+use log::{debug, warn};
+use serde::Deserialize;
+use serde_json::{self, json, Value};
+
+#[derive(Debug, Clone)]
+pub struct RequestEvent {
+    /// 事件发生的时间戳
+    pub time: i64,
+    /// 收到事件的机器人 登陆号
+    pub self_id: i64,
+    /// 上报类型
+    pub post_type: String,
+    /// 请求类型
+    pub request_type: String,
+    /// 解析后的请求，未知或解析失败的请求为 `Request::Unknown`
+    pub request: Request,
+
+    /// 原始的onebot消息，已处理成json格式
+    pub original_json: Value,
+
+    pub(crate) quick_operation: QuickOperation,
+}
+
+#[derive(Deserialize)]
+struct RawRequestEvent {
+    time: i64,
+    self_id: i64,
+    post_type: String,
+    request_type: String,
+}
+
+impl RequestEvent {
+    pub(crate) fn new(
+        api_tx: queue::Sender<ApiAndOneshot>,
+        temp: Value,
+    ) -> Result<RequestEvent, serde_json::Error> {
+        let RawRequestEvent {
+            time,
+            self_id,
+            post_type,
+            request_type,
+        } = RawRequestEvent::deserialize(&temp)?;
+        let request = Request::new(&temp, api_tx);
+        Ok(RequestEvent {
+            time,
+            self_id,
+            post_type,
+            request_type,
+            request,
+            original_json: temp,
+            quick_operation: QuickOperation::default(),
+        })
+    }
+}
+
+impl RequestEvent {
+    /// 如果是加好友请求，返回 `FriendRequest`
+    pub fn as_friend(&self) -> Option<&FriendRequest> {
+        match &self.request {
+            Request::Friend(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// 如果是加群请求／邀请，返回 `GroupRequest`
+    pub fn as_group(&self) -> Option<&GroupRequest> {
+        match &self.request {
+            Request::Group(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// 使用 HTTP POST 快速操作同意请求，省去一次 api 调用
+    ///
+    /// `remark`: 好友备注，仅在好友请求时有效
+    ///
+    /// 只有通过 HTTP POST 上报的事件才能使用快速操作，且每个事件只能使用一次。
+    /// 不能使用时会退回到 `set_friend_add_request` 或 `set_group_add_request` api。
+    pub fn quick_approve(&self, remark: &str) {
+        self.quick_handle(true, remark)
+    }
+
+    /// 使用 HTTP POST 快速操作拒绝请求，省去一次 api 调用
+    ///
+    /// `reason`: 拒绝理由，仅在加群请求时有效
+    ///
+    /// 只有通过 HTTP POST 上报的事件才能使用快速操作，且每个事件只能使用一次。
+    /// 不能使用时会退回到 `set_friend_add_request` 或 `set_group_add_request` api。
+    pub fn quick_reject(&self, reason: &str) {
+        self.quick_handle(false, reason)
+    }
+
+    fn quick_handle(&self, approve: bool, text: &str) {
+        let is_friend = matches!(self.request, Request::Friend(_));
+
+        let operation = match (is_friend, approve) {
+            (true, true) => json!({ "approve": true, "remark": text }),
+            (false, false) => json!({ "approve": false, "reason": text }),
+            (_, approve) => json!({ "approve": approve }),
+        };
+        if self.quick_operation.send(operation) {
+            return;
+        }
+
+        match &self.request {
+            Request::Friend(v) => v.handle(approve, text),
+            Request::Group(v) => v.handle(approve, text),
+            Request::Unknown(_) => warn!("Unknown request type: {}", self.request_type),
+        }
+    }
+}
+
+/// OneBot v11 的请求
+#[derive(Debug, Clone)]
+pub enum Request {
+    /// 加好友请求
+    Friend(FriendRequest),
+    /// 加群请求／邀请
+    Group(GroupRequest),
+    /// 未知或缺少字段的请求，附带原始的 json
+    Unknown(Value),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "request_type", rename_all = "snake_case")]
+enum RawRequest {
+    Friend {
+        user_id: i64,
+        #[serde(default)]
+        comment: String,
+        flag: String,
+    },
+    Group {
+        sub_type: GroupRequestType,
+        group_id: i64,
+        user_id: i64,
+        #[serde(default)]
+        comment: String,
+        flag: String,
+    },
+}
+
+impl Request {
+    fn new(json: &Value, api_tx: queue::Sender<ApiAndOneshot>) -> Request {
+        let raw = match RawRequest::deserialize(json) {
+            Ok(v) => v,
+            Err(e) => {
+                debug!("Failed to parse request: {e}");
+                return Request::Unknown(json.clone());
+            }
+        };
+
+        match raw {
+            RawRequest::Friend {
+                user_id,
+                comment,
+                flag,
+            } => Request::Friend(FriendRequest {
+                user_id,
+                comment,
+                flag,
+                api_tx,
+            }),
+            RawRequest::Group {
+                sub_type,
+                group_id,
+                user_id,
+                comment,
+                flag,
+            } => Request::Group(GroupRequest {
+                sub_type,
+                group_id,
+                user_id,
+                comment,
+                flag,
+                api_tx,
+            }),
+        }
+    }
+}
+
+/// 加好友请求
+#[derive(Debug, Clone)]
+pub struct FriendRequest {
+    /// 发送请求的 QQ 号
+    pub user_id: i64,
+    /// 验证信息
+    pub comment: String,
+    /// 请求 flag，在调用处理请求的 api 时需要传入
+    pub flag: String,
+
+    api_tx: queue::Sender<ApiAndOneshot>,
+}
+
+impl FriendRequest {
+    /// 同意加好友请求
+    ///
+    /// `remark`: 添加后的好友备注，为空则不设置
+    pub fn approve(&self, remark: &str) {
+        self.handle(true, remark)
+    }
+
+    /// 拒绝加好友请求
+    ///
+    /// `reason`: 拒绝理由，OneBot 的 `set_friend_add_request` 不支持，仅为与 `GroupRequest` 保持一致
+    pub fn reject(&self, reason: &str) {
+        debug!("Reject friend request {}: {reason}", self.flag);
+        self.handle(false, "")
+    }
+
+    fn handle(&self, approve: bool, remark: &str) {
+        let send_api = SendApi::new(
+            "set_friend_add_request",
+            json!({
+                "flag": self.flag,
+                "approve": approve,
+                "remark": remark,
+            }),
+            "None",
+        );
+        send_api_request_with_forget(&self.api_tx, send_api);
+    }
+}
+
+/// 加群请求的类型
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupRequestType {
+    /// 加群请求
+    Add,
+    /// 邀请登录号入群
+    Invite,
+}
+
+impl GroupRequestType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GroupRequestType::Add => "add",
+            GroupRequestType::Invite => "invite",
+        }
+    }
+}
+
+/// 加群请求／邀请
+#[derive(Debug, Clone)]
+pub struct GroupRequest {
+    /// 请求子类型，加群请求或邀请登录号入群
+    pub sub_type: GroupRequestType,
+    pub group_id: i64,
+    /// 发送请求的 QQ 号
+    pub user_id: i64,
+    /// 验证信息
+    pub comment: String,
+    /// 请求 flag，在调用处理请求的 api 时需要传入
+    pub flag: String,
+
+    api_tx: queue::Sender<ApiAndOneshot>,
+}
+
+impl GroupRequest {
+    /// 同意加群请求／邀请
+    ///
+    /// `remark`: 备注，OneBot 的 `set_group_add_request` 不支持，仅为与 `FriendRequest` 保持一致
+    pub fn approve(&self, remark: &str) {
+        debug!("Approve group request {}: {remark}", self.flag);
+        self.handle(true, "")
+    }
+
+    /// 拒绝加群请求／邀请
+    ///
+    /// `reason`: 拒绝理由，为空则不设置
+    pub fn reject(&self, reason: &str) {
+        self.handle(false, reason)
+    }
+
+    fn handle(&self, approve: bool, reason: &str) {
+        let sub_type = self.sub_type.as_str();
+        let send_api = SendApi::new(
+            "set_group_add_request",
+            json!({
+                "flag": self.flag,
+                "sub_type": sub_type,
+                "type": sub_type,
+                "approve": approve,
+                "reason": reason,
+            }),
+            "None",
+        );
+        send_api_request_with_forget(&self.api_tx, send_api);
+    }
+}
+
+#[test]
+fn parse_request() {
+    let (api_tx, _api_rx) = queue::channel("api", &Default::default());
+    let e = RequestEvent::new(
+        api_tx,
+        serde_json::from_str(r#"{"time":1,"self_id":2,"post_type":"request","request_type":"group","sub_type":"invite","group_id":3,"user_id":4,"comment":"hi","flag":"f"}"#).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(e.request_type, "group");
+    let r = e.as_group().unwrap();
+    assert_eq!(r.sub_type, GroupRequestType::Invite);
+    assert_eq!((r.group_id, r.user_id), (3, 4));
+    assert_eq!((r.comment.as_str(), r.flag.as_str()), ("hi", "f"));
+    assert!(e.as_friend().is_none());
+}
+
+#[test]
+fn parse_malformed_request() {
+    let (api_tx, _api_rx) = queue::channel("api", &Default::default());
+    // 缺少 flag 的请求无法处理，不能解析成 flag 为空的好友请求
+    let e = RequestEvent::new(
+        api_tx,
+        serde_json::from_str(
+            r#"{"time":1,"self_id":2,"post_type":"request","request_type":"friend","user_id":4}"#,
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert!(e.as_friend().is_none());
+    assert!(matches!(&e.request, Request::Unknown(v) if v["user_id"] == 4));
+}
@@ -0,0 +1,624 @@
+//! 账号的事件队列与 api 队列。
+//!
+//! 队列有固定的容量，满了以后按照配置的 [`OverflowPolicy`] 处理。
+//!
+//! 高优先级的元素总是先取出；同一优先级中，每个插件的元素按照进入的顺序取出，不同插件之间轮流取出，
+//! 避免一个插件的批量发送占满队列。同一插件以同一优先级发往同一目标的消息不会乱序。
+
+use super::{OverflowPolicy, QueueOptions, PLUGIN_NAME};
+use crate::error::QueueError;
+use log::warn;
+use parking_lot::{Condvar, Mutex};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::pin::pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::runtime::Handle;
+use tokio::sync::Notify;
+
+/// 队列的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueStats {
+    /// 当前排队的数量
+    pub len: usize,
+    /// 队列容量
+    pub capacity: usize,
+    /// 曾经达到的最大排队数量
+    pub high_water: usize,
+    /// 因 `DropOldest` 或 `DropNewest` 丢弃的数量
+    pub dropped: u64,
+    /// 因 `Error` 被拒绝的数量
+    pub rejected: u64,
+}
+
+/// 队列的计数，不持有队列，账号可以一直保存
+#[derive(Debug)]
+struct Counters {
+    len: AtomicUsize,
+    capacity: usize,
+    high_water: AtomicUsize,
+    dropped: AtomicU64,
+    rejected: AtomicU64,
+}
+
+/// 读取队列状态，不会阻止队列关闭
+#[derive(Debug, Clone)]
+pub struct QueueMonitor {
+    counters: Arc<Counters>,
+}
+
+impl QueueMonitor {
+    pub fn stats(&self) -> QueueStats {
+        let counters = &self.counters;
+        QueueStats {
+            len: counters.len.load(Ordering::Relaxed),
+            capacity: counters.capacity,
+            high_water: counters.high_water.load(Ordering::Relaxed),
+            dropped: counters.dropped.load(Ordering::Relaxed),
+            rejected: counters.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 元素的优先级
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// 管理、处理违规等紧急的操作，总是最先发送
+    High,
+    #[default]
+    Normal,
+    /// 群发等批量操作，没有其他元素时才发送
+    Low,
+}
+
+/// 元素来自哪个插件，插件之外为 `None`
+type Owner = Option<Arc<String>>;
+
+/// 同一优先级的元素，每个插件一个先进先出的队列，轮流取出
+struct Lane<T> {
+    owners: VecDeque<(Owner, VecDeque<(u64, T)>)>,
+}
+
+impl<T> Lane<T> {
+    fn push(&mut self, owner: &Owner, seq: u64, value: T) {
+        match self.owners.iter_mut().find(|(v, _)| v == owner) {
+            Some((_, items)) => items.push_back((seq, value)),
+            None => self
+                .owners
+                .push_back((owner.clone(), VecDeque::from([(seq, value)]))),
+        }
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        let (owner, mut items) = self.owners.pop_front()?;
+        let value = items.pop_front().map(|(_, v)| v);
+        if !items.is_empty() {
+            // 轮到下一个插件
+            self.owners.push_back((owner, items));
+        }
+        value
+    }
+
+    /// 取出此优先级中最早进入的元素
+    fn pop_oldest(&mut self) -> Option<T> {
+        let (index, _) = self
+            .owners
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (_, items))| Some((i, items.front()?.0)))
+            .min_by_key(|(_, seq)| *seq)?;
+        let items = &mut self.owners[index].1;
+        let value = items.pop_front().map(|(_, v)| v);
+        if items.is_empty() {
+            self.owners.remove(index);
+        }
+        value
+    }
+}
+
+struct Items<T> {
+    /// 按照 `Priority` 的顺序
+    lanes: [Lane<T>; 3],
+    len: usize,
+    seq: u64,
+}
+
+impl<T> Items<T> {
+    fn new() -> Self {
+        Items {
+            lanes: std::array::from_fn(|_| Lane {
+                owners: VecDeque::new(),
+            }),
+            len: 0,
+            seq: 0,
+        }
+    }
+
+    fn push(&mut self, priority: Priority, owner: &Owner, value: T) {
+        self.seq += 1;
+        self.lanes[priority as usize].push(owner, self.seq, value);
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        let value = self.lanes.iter_mut().find_map(|lane| lane.pop())?;
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// 丢弃时使用，取出优先级最低的元素中最早进入的
+    fn pop_oldest(&mut self) -> Option<T> {
+        let value = self
+            .lanes
+            .iter_mut()
+            .rev()
+            .find_map(|lane| lane.pop_oldest())?;
+        self.len -= 1;
+        Some(value)
+    }
+}
+
+struct State<T> {
+    items: Items<T>,
+    /// `Block` 时在 tokio 运行时中同步放入、队列已满的元素。不阻塞线程，按顺序等待队列有空位，
+    /// 之后放入的元素排在它们后面。最多与容量相同
+    deferred: VecDeque<(Priority, Owner, T)>,
+    senders: usize,
+    receiver_closed: bool,
+}
+
+impl<T> State<T> {
+    /// 包括等待放入的元素
+    fn len(&self) -> usize {
+        self.items.len + self.deferred.len()
+    }
+}
+
+struct Shared<T> {
+    name: &'static str,
+    overflow: OverflowPolicy,
+    state: Mutex<State<T>>,
+    counters: Arc<Counters>,
+    /// 有新元素，或者所有发送端已关闭
+    recv_notify: Notify,
+    /// 队列有空位，或者接收端已关闭。分别唤醒异步与同步等待的发送端
+    send_notify: Notify,
+    send_cond: Condvar,
+}
+
+impl<T> Shared<T> {
+    /// 放入一个元素，队列满时按照 `overflow` 处理。`Block` 时返回 `Full`，由调用者等待
+    fn try_push(
+        &self,
+        state: &mut State<T>,
+        (priority, owner): (Priority, &Owner),
+        value: T,
+        overflow: OverflowPolicy,
+    ) -> Result<Option<T>, QueueError<T>> {
+        if state.receiver_closed {
+            return Err(QueueError::Closed(value));
+        }
+        let counters = &self.counters;
+
+        let mut evicted = None;
+        // 只有 `Block` 时 `deferred` 不为空，此时需要排在它们后面
+        if state.items.len >= counters.capacity || !state.deferred.is_empty() {
+            match overflow {
+                OverflowPolicy::Block => return Err(QueueError::Full(value)),
+                OverflowPolicy::Error => {
+                    counters.rejected.fetch_add(1, Ordering::Relaxed);
+                    warn!("{} queue is full, rejected a new item", self.name);
+                    return Err(QueueError::Full(value));
+                }
+                OverflowPolicy::DropNewest => {
+                    counters.dropped.fetch_add(1, Ordering::Relaxed);
+                    warn!("{} queue is full, dropped the newest item", self.name);
+                    return Ok(Some(value));
+                }
+                OverflowPolicy::DropOldest => {
+                    counters.dropped.fetch_add(1, Ordering::Relaxed);
+                    warn!("{} queue is full, dropped the oldest item", self.name);
+                    evicted = state.items.pop_oldest();
+                }
+            }
+        }
+
+        state.items.push(priority, owner, value);
+        self.update_len(state);
+        self.recv_notify.notify_one();
+        Ok(evicted)
+    }
+
+    fn update_len(&self, state: &State<T>) {
+        let len = state.len();
+        self.counters.len.store(len, Ordering::Relaxed);
+        self.counters.high_water.fetch_max(len, Ordering::Relaxed);
+    }
+
+    fn wake_senders(&self) {
+        self.send_notify.notify_waiters();
+        self.send_cond.notify_all();
+    }
+}
+
+/// 队列的发送端
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+    /// 通过此发送端放入的元素的优先级
+    priority: Priority,
+}
+
+/// 队列的接收端
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// 按照配置创建一个队列，`name` 用于日志
+pub(crate) fn channel<T>(name: &'static str, options: &QueueOptions) -> (Sender<T>, Receiver<T>) {
+    let capacity = options.capacity.max(1);
+    let shared = Arc::new(Shared {
+        name,
+        overflow: options.overflow,
+        state: Mutex::new(State {
+            items: Items::new(),
+            deferred: VecDeque::new(),
+            senders: 1,
+            receiver_closed: false,
+        }),
+        counters: Arc::new(Counters {
+            len: AtomicUsize::new(0),
+            capacity,
+            high_water: AtomicUsize::new(0),
+            dropped: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        }),
+        recv_notify: Notify::new(),
+        send_notify: Notify::new(),
+        send_cond: Condvar::new(),
+    });
+    (
+        Sender {
+            shared: shared.clone(),
+            priority: Priority::default(),
+        },
+        Receiver { shared },
+    )
+}
+
+impl<T> Sender<T> {
+    /// 放入一个元素，队列满时按照配置的 `OverflowPolicy` 处理。
+    ///
+    /// 返回 `Ok(Some(v))` 表示有元素被丢弃：`DropOldest` 时是优先级最低的元素中最早进入的，
+    /// `DropNewest` 时是 `value` 本身。
+    ///
+    /// `Block` 时：
+    /// - 在 tokio 运行时中不会阻塞，元素交给队列按顺序等待空位，返回 `Ok(None)`。
+    ///   之后通过 `push` 或 [`Sender::send`] 放入的元素排在它后面。等待的元素最多与容量相同，
+    ///   超过后返回 `QueueError::Full`
+    /// - 在运行时之外阻塞当前线程直到队列有空位
+    pub fn push(&self, value: T) -> Result<Option<T>, QueueError<T>> {
+        let shared = &*self.shared;
+        let owner = current_owner();
+        let slot = (self.priority, &owner);
+        let mut state = shared.state.lock();
+        let mut value = match shared.try_push(&mut state, slot, value, shared.overflow) {
+            Err(QueueError::Full(v)) if shared.overflow == OverflowPolicy::Block => v,
+            r => return r,
+        };
+
+        if Handle::try_current().is_ok() {
+            if state.deferred.len() >= shared.counters.capacity {
+                shared.counters.rejected.fetch_add(1, Ordering::Relaxed);
+                warn!("{} queue is full, rejected a new item", shared.name);
+                return Err(QueueError::Full(value));
+            }
+            state.deferred.push_back((self.priority, owner, value));
+            shared.update_len(&state);
+            return Ok(None);
+        }
+
+        loop {
+            shared.send_cond.wait(&mut state);
+            match shared.try_push(&mut state, slot, value, OverflowPolicy::Block) {
+                Err(QueueError::Full(v)) => value = v,
+                r => return r,
+            }
+        }
+    }
+
+    /// 放入一个元素，`Block` 时异步等待队列有空位，其他策略与 [`Sender::push`] 相同
+    pub async fn send(&self, value: T) -> Result<Option<T>, QueueError<T>> {
+        let shared = &*self.shared;
+        let owner = current_owner();
+        let mut value = value;
+        loop {
+            let mut notified = pin!(shared.send_notify.notified());
+            notified.as_mut().enable();
+            {
+                let mut state = shared.state.lock();
+                match shared.try_push(&mut state, (self.priority, &owner), value, shared.overflow) {
+                    Err(QueueError::Full(v)) if shared.overflow == OverflowPolicy::Block => {
+                        value = v
+                    }
+                    r => return r,
+                }
+            }
+            notified.await;
+        }
+    }
+
+    /// 忽略容量放入一个元素，只用于断开、关闭等不能丢弃的内部事件
+    ///
+    /// 以 `Priority::Normal` 放入，排在此前收到的事件之后。
+    pub(crate) fn force_send(&self, value: T) -> Result<(), QueueError<T>> {
+        let shared = &*self.shared;
+        let mut state = shared.state.lock();
+        if state.receiver_closed {
+            return Err(QueueError::Closed(value));
+        }
+        if state.deferred.is_empty() {
+            state.items.push(Priority::Normal, &None, value);
+        } else {
+            state.deferred.push_back((Priority::Normal, None, value));
+        }
+        shared.update_len(&state);
+        shared.recv_notify.notify_one();
+        Ok(())
+    }
+
+    /// 获取一个以 `priority` 放入元素的发送端，与此发送端使用同一个队列
+    pub fn with_priority(&self, priority: Priority) -> Sender<T> {
+        let mut sender = self.clone();
+        sender.priority = priority;
+        sender
+    }
+
+    /// 通过此发送端放入的元素的优先级
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    /// 队列的状态
+    pub fn stats(&self) -> QueueStats {
+        self.monitor().stats()
+    }
+
+    /// 当前排队的数量
+    pub fn len(&self) -> usize {
+        self.shared.counters.len.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 获取可以一直保存的状态读取器
+    pub fn monitor(&self) -> QueueMonitor {
+        QueueMonitor {
+            counters: self.shared.counters.clone(),
+        }
+    }
+
+    /// 接收端是否已关闭
+    pub fn is_closed(&self) -> bool {
+        self.shared.state.lock().receiver_closed
+    }
+
+    /// 是否与 `other` 是同一个队列
+    pub fn same_channel(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().senders += 1;
+        Sender {
+            shared: self.shared.clone(),
+            priority: self.priority,
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.shared.recv_notify.notify_one();
+        }
+    }
+}
+
+/// 在插件中放入的元素属于此插件
+fn current_owner() -> Owner {
+    PLUGIN_NAME.try_with(|name| name.clone()).ok()
+}
+
+impl<T> Debug for Sender<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sender")
+            .field("name", &self.shared.name)
+            .field("priority", &self.priority)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl<T> Receiver<T> {
+    /// 按顺序取出下一个元素，队列为空且所有发送端关闭后返回 `None`
+    pub async fn recv(&mut self) -> Option<T> {
+        let shared = &*self.shared;
+        loop {
+            let notified = shared.recv_notify.notified();
+            {
+                let mut state = shared.state.lock();
+                if let Some(v) = state.items.pop() {
+                    // 空出的位置先给等待放入的元素
+                    if let Some((priority, owner, v)) = state.deferred.pop_front() {
+                        state.items.push(priority, &owner, v);
+                    }
+                    shared.update_len(&state);
+                    if state.deferred.is_empty() {
+                        shared.wake_senders();
+                    }
+                    return Some(v);
+                }
+                if state.senders == 0 {
+                    return None;
+                }
+            }
+            notified.await;
+        }
+    }
+
+    /// 当前排队的数量
+    pub fn len(&self) -> usize {
+        self.shared.counters.len.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().receiver_closed = true;
+        self.shared.wake_senders();
+    }
+}
+
+impl<T> Debug for Receiver<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Receiver")
+            .field("name", &self.shared.name)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[tokio::test]
+async fn queue_overflow() {
+    let options = |capacity, overflow| QueueOptions { capacity, overflow };
+
+    let (tx, mut rx) = channel("test", &options(2, OverflowPolicy::DropOldest));
+    assert!(matches!(tx.push(1), Ok(None)));
+    assert!(matches!(tx.push(2), Ok(None)));
+    assert!(matches!(tx.push(3), Ok(Some(1))));
+    assert_eq!(tx.stats().dropped, 1);
+    assert_eq!((rx.recv().await, rx.recv().await), (Some(2), Some(3)));
+
+    let (tx, mut rx) = channel("test", &options(1, OverflowPolicy::DropNewest));
+    assert!(matches!(tx.push(1), Ok(None)));
+    assert!(matches!(tx.push(2), Ok(Some(2))));
+    assert_eq!(rx.recv().await, Some(1));
+
+    let (tx, rx) = channel("test", &options(1, OverflowPolicy::Error));
+    assert!(matches!(tx.push(1), Ok(None)));
+    assert!(matches!(tx.push(2), Err(QueueError::Full(2))));
+    assert_eq!(tx.stats().rejected, 1);
+    drop(rx);
+    assert!(matches!(tx.push(3), Err(QueueError::Closed(3))));
+}
+
+#[tokio::test]
+async fn queue_priority_and_fairness() {
+    let (tx, mut rx) = channel("test", &QueueOptions::default());
+    let bulk = tx.with_priority(Priority::Low);
+    let urgent = tx.with_priority(Priority::High);
+
+    let a = Arc::new(String::from("a"));
+    let b = Arc::new(String::from("b"));
+    PLUGIN_NAME
+        .scope(a, async {
+            for i in 0..3 {
+                bulk.push(i).unwrap();
+                tx.push(10 + i).unwrap();
+            }
+        })
+        .await;
+    PLUGIN_NAME
+        .scope(b, async {
+            for i in 0..2 {
+                tx.push(20 + i).unwrap();
+            }
+            urgent.push(30).unwrap();
+        })
+        .await;
+
+    let mut out = Vec::new();
+    while !rx.is_empty() {
+        out.push(rx.recv().await.unwrap());
+    }
+    // 高优先级最先，同一优先级中两个插件轮流，低优先级最后
+    assert_eq!(out, [30, 10, 20, 11, 21, 12, 0, 1, 2]);
+}
+
+#[tokio::test]
+async fn queue_block_keeps_order() {
+    let (tx, mut rx) = channel("test", &QueueOptions {
+        capacity: 4,
+        overflow: OverflowPolicy::Block,
+    });
+    // 单线程运行时中同步放入不会阻塞，等待的元素最多与容量相同
+    for i in 0..8 {
+        assert!(matches!(tx.push(i), Ok(None)));
+    }
+    assert!(matches!(tx.push(8), Err(QueueError::Full(8))));
+    assert_eq!(tx.len(), 8);
+    assert_eq!(tx.stats().rejected, 1);
+
+    // 异步放入的元素排在等待的元素后面
+    let producer = tokio::spawn(async move {
+        for i in 8..100 {
+            tx.send(i).await.unwrap();
+        }
+    });
+
+    let mut received = Vec::new();
+    while let Some(v) = rx.recv().await {
+        received.push(v);
+    }
+    producer.await.unwrap();
+    assert_eq!(received, (0..100).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn queue_block_outside_runtime() {
+    let (tx, mut rx) = channel("test", &QueueOptions {
+        capacity: 4,
+        overflow: OverflowPolicy::Block,
+    });
+    // 运行时之外的线程阻塞等待
+    let producer = std::thread::spawn(move || {
+        for i in 0..100 {
+            tx.push(i).unwrap();
+        }
+    });
+
+    let mut received = Vec::new();
+    while let Some(v) = rx.recv().await {
+        assert!(rx.len() <= 4);
+        received.push(v);
+    }
+    producer.join().unwrap();
+    assert_eq!(received, (0..100).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn queue_force_send_keeps_order() {
+    let (tx, mut rx) = channel("test", &QueueOptions {
+        capacity: 2,
+        overflow: OverflowPolicy::DropNewest,
+    });
+    tx.push("event 1").unwrap();
+    tx.push("event 2").unwrap();
+    // 队列已满时断开事件仍然放入，但不能排到此前的事件前面
+    tx.force_send("disconnected").unwrap();
+
+    assert_eq!(rx.recv().await, Some("event 1"));
+    assert_eq!(rx.recv().await, Some("event 2"));
+    assert_eq!(rx.recv().await, Some("disconnected"));
+}
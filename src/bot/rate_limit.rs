@@ -0,0 +1,405 @@
+//! api 发送限速，避免账号因为发送过快被风控。
+//!
+//! 使用令牌桶，按照 api 的类别分别限速，每个类别可以设置全局、每个群、每个用户的速率。
+//! 超过速率的 api 会在队列中等待，不会被丢弃。
+
+use super::{queue, ApiAndOneshot, Rate, RateLimit, RateLimitRule, SendApi};
+use ahash::RandomState;
+use log::debug;
+use rand::Rng;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// 超过这个数量时清理已经回满的桶
+const BUCKET_SWEEP_LEN: usize = 1024;
+
+/// api 的类别，分别限速
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum ApiClass {
+    /// `send_` 开头的 api，如 `send_msg`、`send_group_forward_msg`
+    SendMsg,
+    /// `get_` 与 `can_` 开头的查询
+    Query,
+    /// 其余的 api，如群管理
+    Other,
+}
+
+impl ApiClass {
+    pub(crate) fn of(action: &str) -> ApiClass {
+        if action.starts_with("send_") {
+            ApiClass::SendMsg
+        } else if action.starts_with("get_") || action.starts_with("can_") {
+            ApiClass::Query
+        } else {
+            ApiClass::Other
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(rate: &Rate, now: Instant) -> Self {
+        Bucket {
+            tokens: rate.burst.max(1) as f64,
+            last: now,
+        }
+    }
+
+    fn refill(&mut self, rate: &Rate, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate.per_second).min(rate.burst.max(1) as f64);
+        self.last = now;
+    }
+
+    /// 取出一个令牌，令牌不足时预支，返回需要等待的时间
+    fn reserve(&mut self, rate: &Rate, now: Instant) -> Duration {
+        self.refill(rate, now);
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate.per_second)
+        }
+    }
+}
+
+#[derive(Default)]
+struct KeyedBuckets {
+    buckets: HashMap<i64, Bucket, RandomState>,
+}
+
+impl KeyedBuckets {
+    fn reserve(&mut self, key: i64, rate: &Rate, now: Instant) -> Duration {
+        if self.buckets.len() >= BUCKET_SWEEP_LEN {
+            let burst = rate.burst.max(1) as f64;
+            self.buckets.retain(|_, bucket| {
+                bucket.refill(rate, now);
+                bucket.tokens < burst
+            });
+        }
+        self.buckets
+            .entry(key)
+            .or_insert_with(|| Bucket::new(rate, now))
+            .reserve(rate, now)
+    }
+}
+
+/// 一个类别的限速
+struct ClassLimiter {
+    rule: RateLimitRule,
+    global: Option<Bucket>,
+    groups: KeyedBuckets,
+    users: KeyedBuckets,
+}
+
+impl ClassLimiter {
+    fn new(rule: RateLimitRule) -> Self {
+        ClassLimiter {
+            rule,
+            global: None,
+            groups: KeyedBuckets::default(),
+            users: KeyedBuckets::default(),
+        }
+    }
+
+    fn reserve(&mut self, params: &Value, now: Instant) -> Duration {
+        let mut wait = Duration::ZERO;
+
+        if let Some(rate) = valid(&self.rule.global) {
+            let bucket = self.global.get_or_insert_with(|| Bucket::new(rate, now));
+            wait = wait.max(bucket.reserve(rate, now));
+        }
+        if let (Some(rate), Some(group_id)) = (valid(&self.rule.group), id_of(params, "group_id")) {
+            wait = wait.max(self.groups.reserve(group_id, rate, now));
+        }
+        if let (Some(rate), Some(user_id)) = (valid(&self.rule.user), id_of(params, "user_id")) {
+            wait = wait.max(self.users.reserve(user_id, rate, now));
+        }
+
+        if self.rule.jitter > 0 {
+            let jitter = rand::thread_rng().gen_range(0..=self.rule.jitter);
+            wait += Duration::from_millis(jitter);
+        }
+        wait
+    }
+}
+
+/// 速率不大于 0 时不限速
+fn valid(rate: &Option<Rate>) -> Option<&Rate> {
+    rate.as_ref().filter(|rate| rate.per_second > 0.0)
+}
+
+/// 部分实现的 id 是字符串
+fn id_of(params: &Value, key: &str) -> Option<i64> {
+    match params.get(key)? {
+        Value::Number(v) => v.as_i64(),
+        Value::String(v) => v.parse().ok(),
+        _ => None,
+    }
+}
+
+/// 一个账号的 api 限速
+pub(crate) struct RateLimiter {
+    send_msg: ClassLimiter,
+    query: ClassLimiter,
+    other: ClassLimiter,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(conf: &RateLimit) -> Self {
+        RateLimiter {
+            send_msg: ClassLimiter::new(conf.send_msg.clone()),
+            query: ClassLimiter::new(conf.query.clone()),
+            other: ClassLimiter::new(conf.other.clone()),
+        }
+    }
+
+    /// 为 api 预留发送的额度，返回发送前需要等待的时间
+    pub(crate) fn reserve(&mut self, api: &SendApi, now: Instant) -> Duration {
+        let limiter = match ApiClass::of(&api.action) {
+            ApiClass::SendMsg => &mut self.send_msg,
+            ApiClass::Query => &mut self.query,
+            ApiClass::Other => &mut self.other,
+        };
+        limiter.reserve(&api.params, now)
+    }
+}
+
+/// 同时等待限速的 api 的最大数量，超过后不再从队列中取出，由队列的 `OverflowPolicy` 处理
+const WAITING_LIMIT: usize = 1024;
+
+/// 等待限速的 api 按照类别与目标分开排队，同一队列中的 api 按顺序发送
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct WaitingKey {
+    class: ApiClass,
+    group_id: Option<i64>,
+    user_id: Option<i64>,
+}
+
+impl WaitingKey {
+    fn of(api: &SendApi) -> Self {
+        WaitingKey {
+            class: ApiClass::of(&api.action),
+            group_id: id_of(&api.params, "group_id"),
+            user_id: id_of(&api.params, "user_id"),
+        }
+    }
+}
+
+/// 连接使用的 api 队列接收端，按照配置限速
+///
+/// 超过速率的 api 按照类别与目标分开等待，不会阻塞其他群、用户或类别的 api。
+pub(crate) struct ApiReceiver {
+    rx: queue::Receiver<ApiAndOneshot>,
+    limiter: Option<RateLimiter>,
+    /// 正在等待限速的 api 与可以发送的时间。`recv` 被取消时保留，下次继续等待
+    waiting: HashMap<WaitingKey, VecDeque<(Instant, ApiAndOneshot)>, RandomState>,
+    waiting_len: usize,
+    /// 队列已经关闭，只发送剩余的 api
+    closed: bool,
+}
+
+impl ApiReceiver {
+    pub(crate) fn new(rx: queue::Receiver<ApiAndOneshot>, conf: &RateLimit) -> Self {
+        ApiReceiver {
+            rx,
+            limiter: conf.enable.then(|| RateLimiter::new(conf)),
+            waiting: HashMap::with_hasher(RandomState::new()),
+            waiting_len: 0,
+            closed: false,
+        }
+    }
+
+    /// 取出下一个可以发送的 api，超过速率时等待。此方法可以安全地在 `select!` 中使用
+    pub(crate) async fn recv(&mut self) -> Option<ApiAndOneshot> {
+        if self.limiter.is_none() {
+            return self.rx.recv().await;
+        }
+
+        loop {
+            if let Some(api) = self.pop_ready(Instant::now()) {
+                return Some(api);
+            }
+            let deadline = self.next_deadline();
+            if self.closed && deadline.is_none() {
+                return None;
+            }
+
+            let can_recv = !self.closed && self.waiting_len < WAITING_LIMIT;
+            tokio::select! {
+                api = self.rx.recv(), if can_recv => match api {
+                    Some(api) => {
+                        if let Some(api) = self.admit(api, Instant::now()) {
+                            return Some(api);
+                        }
+                    }
+                    None => self.closed = true,
+                },
+                _ = sleep_until_some(deadline) => {}
+            }
+        }
+    }
+
+    /// 为 api 预留额度，可以立即发送时返回 api，否则放入等待队列
+    fn admit(&mut self, api: ApiAndOneshot, now: Instant) -> Option<ApiAndOneshot> {
+        let limiter = self.limiter.as_mut()?;
+        let wait = limiter.reserve(&api.0, now);
+        let queue = self.waiting.entry(WaitingKey::of(&api.0)).or_default();
+
+        // 同一队列中前面还有 api 时不能插队
+        if wait.is_zero() && queue.is_empty() {
+            return Some(api);
+        }
+        debug!("Api {} rate limited, waiting {wait:?}", api.0.action);
+        let deadline = match queue.back() {
+            Some((last, _)) => (now + wait).max(*last),
+            None => now + wait,
+        };
+        queue.push_back((deadline, api));
+        self.waiting_len += 1;
+        None
+    }
+
+    /// 取出已经到时间的 api 中最早的一个
+    fn pop_ready(&mut self, now: Instant) -> Option<ApiAndOneshot> {
+        let key = self
+            .waiting
+            .iter()
+            .filter_map(|(key, queue)| Some((*key, queue.front()?.0)))
+            .filter(|(_, deadline)| *deadline <= now)
+            .min_by_key(|(_, deadline)| *deadline)?
+            .0;
+
+        let queue = self.waiting.get_mut(&key)?;
+        let (_, api) = queue.pop_front()?;
+        if queue.is_empty() {
+            self.waiting.remove(&key);
+        }
+        self.waiting_len -= 1;
+        Some(api)
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.waiting
+            .values()
+            .filter_map(|queue| queue.front().map(|(deadline, _)| *deadline))
+            .min()
+    }
+}
+
+async fn sleep_until_some(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[test]
+fn token_bucket() {
+    use serde_json::json;
+
+    let conf = RateLimit {
+        enable: true,
+        send_msg: RateLimitRule {
+            global: Some(Rate {
+                per_second: 10.0,
+                burst: 2,
+            }),
+            group: Some(Rate {
+                per_second: 1.0,
+                burst: 1,
+            }),
+            user: None,
+            jitter: 0,
+        },
+        query: RateLimitRule::default(),
+        other: RateLimitRule::default(),
+    };
+    let mut limiter = RateLimiter::new(&conf);
+    let now = Instant::now();
+    let api =
+        |action: &str, group_id: i64| SendApi::new(action, json!({ "group_id": group_id }), "None");
+
+    assert_eq!(
+        limiter.reserve(&api("send_group_msg", 1), now),
+        Duration::ZERO
+    );
+    // 同一个群超过速率
+    assert_eq!(
+        limiter.reserve(&api("send_group_msg", 1), now),
+        Duration::from_secs(1)
+    );
+    // 全局超过速率
+    assert_eq!(
+        limiter.reserve(&api("send_group_msg", 2), now),
+        Duration::from_millis(100)
+    );
+    // 查询不受发送消息的限制
+    assert_eq!(
+        limiter.reserve(&api("get_group_info", 1), now),
+        Duration::ZERO
+    );
+    // 令牌恢复后不再等待
+    let later = now + Duration::from_secs(3);
+    assert_eq!(
+        limiter.reserve(&api("send_group_msg", 3), later),
+        Duration::ZERO
+    );
+}
+
+#[tokio::test]
+async fn throttled_group_does_not_block_others() {
+    use serde_json::json;
+    use std::time::Duration;
+
+    let conf = RateLimit {
+        enable: true,
+        send_msg: RateLimitRule {
+            global: None,
+            group: Some(Rate {
+                per_second: 1.0,
+                burst: 1,
+            }),
+            user: None,
+            jitter: 0,
+        },
+        query: RateLimitRule::default(),
+        other: RateLimitRule::default(),
+    };
+    let (tx, rx) = queue::channel("api", &Default::default());
+    let mut rx = ApiReceiver::new(rx, &conf);
+
+    let api = |action: &str, group_id: i64| {
+        let api = SendApi::new(action, json!({ "group_id": group_id }), "None");
+        tx.push((api, None)).unwrap();
+    };
+    api("send_group_msg", 1);
+    api("send_group_msg", 1);
+    api("send_group_msg", 1);
+    api("get_group_info", 1);
+    api("send_group_msg", 2);
+
+    let mut sent = Vec::new();
+    for _ in 0..3 {
+        let (api, _) = tokio::time::timeout(Duration::from_millis(200), rx.recv())
+            .await
+            .expect("api should not be delayed by another group")
+            .unwrap();
+        sent.push((api.action, api.params["group_id"].as_i64().unwrap()));
+    }
+    assert_eq!(sent, [
+        ("send_group_msg".to_string(), 1),
+        ("get_group_info".to_string(), 1),
+        ("send_group_msg".to_string(), 2),
+    ]);
+
+    // 被限速的群仍然按顺序等待
+    let r = tokio::time::timeout(Duration::from_millis(200), rx.recv()).await;
+    assert!(r.is_err());
+}
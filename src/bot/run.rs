@@ -1,17 +1,22 @@
 use super::{
     handler::{InternalEvent, KoviEvent},
-    ApiAndOneshot, Bot, BotPlugin,
+    queue,
+    rate_limit::ApiReceiver,
+    ApiAndOneshot, Bot, BotAccount, BotPlugin, Server,
 };
 use crate::{
     bot::{PLUGIN_BUILDER, PLUGIN_NAME},
     PluginBuilder,
 };
-use log::error;
+use log::{error, warn};
 use std::{
     borrow::Borrow,
     future::Future,
     process::exit,
-    sync::{Arc, LazyLock, RwLock},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, LazyLock, RwLock,
+    },
 };
 use tokio::{
     runtime::Runtime,
@@ -37,40 +42,84 @@ impl Bot {
 
     /// 运行bot
     ///
-    /// **注意此函数会阻塞, 直到Bot连接失效，或者有退出信号传入程序**
+    /// **注意此函数会阻塞, 直到所有账号的连接失效，或者有退出信号传入程序**
     pub fn run(self) {
-        let server = self.information.server.clone();
-
+        let servers: Vec<Server> = std::iter::once(self.information.server.clone())
+            .chain(self.information.servers.iter().cloned())
+            .collect();
+
+        self.publish_listen();
+        let listen = self.listen.clone();
+        let queue_config = self.information.queue.clone();
+        let rate_limit = self.information.rate_limit.clone();
         let bot = Arc::new(RwLock::new(self));
 
         RUNTIME.block_on(async {
-            //处理连接，从msg_tx返回消息
+            // Bot 级别的事件，目前只有关闭事件，不需要配置队列
             let (event_tx, mut event_rx): (
                 mpsc::Sender<InternalEvent>,
                 mpsc::Receiver<InternalEvent>,
             ) = mpsc::channel(32);
 
-            // 接收插件的api
-            let (api_tx, api_rx): (mpsc::Sender<ApiAndOneshot>, mpsc::Receiver<ApiAndOneshot>) =
-                mpsc::channel(32);
-
-            // 连接
-            let connect_task = tokio::spawn({
-                let event_tx = event_tx.clone();
-                Self::ws_connect(server, api_rx, event_tx, bot.clone())
-            });
-
-            let connect_res = connect_task.await.unwrap();
+            // 每个账号独立连接，一个账号连接失败不影响其他账号
+            let mut accounts = Vec::new();
+            for server in servers {
+                let (host, port) = (server.host.clone(), server.port);
+
+                //处理连接，从account_event_tx返回消息
+                let (account_event_tx, account_event_rx): (
+                    queue::Sender<InternalEvent>,
+                    queue::Receiver<InternalEvent>,
+                ) = queue::channel("Event", &queue_config.event);
+                let event_queue = account_event_tx.monitor();
+
+                // 接收插件的api
+                let (api_tx, api_rx): (
+                    queue::Sender<ApiAndOneshot>,
+                    queue::Receiver<ApiAndOneshot>,
+                ) = queue::channel("Api", &queue_config.api);
+
+                // 连接
+                let connect_res = tokio::spawn(Self::connect(
+                    server,
+                    ApiReceiver::new(api_rx, &rate_limit),
+                    account_event_tx,
+                    bot.clone(),
+                ))
+                .await
+                .unwrap();
+
+                if let Err(e) = connect_res {
+                    error!(
+                        "{e}\nBot connection to {host}:{port} failed, please check the configuration and restart the bot"
+                    );
+                    continue;
+                }
 
-            if let Err(e) = connect_res {
-                error!(
-                    "{e}\nBot connection failed, please check the configuration and restart the bot"
-                );
-                return;
+                let account = Arc::new(BotAccount::new(host, port, api_tx, event_queue, listen.clone()));
+                accounts.push((account, account_event_rx));
             }
 
+            // 第一个连接成功的账号作为插件默认使用的账号
+            let default_account = match accounts.first() {
+                Some((account, _)) => account.clone(),
+                None => return,
+            };
+
             {
                 let mut bot_write = bot.write().unwrap();
+                bot_write.default_account = Some(default_account.clone());
+
+                let remaining = Arc::new(AtomicUsize::new(accounts.len()));
+                for (account, account_event_rx) in accounts {
+                    bot_write.spawn(Self::account_event_loop(
+                        bot.clone(),
+                        account,
+                        account_event_rx,
+                        event_tx.clone(),
+                        remaining.clone(),
+                    ));
+                }
 
                 // drop检测
                 bot_write.spawn({
@@ -81,23 +130,23 @@ impl Bot {
                 // 运行所有的main
                 bot_write.spawn({
                     let bot = bot.clone();
-                    let api_tx = api_tx.clone();
-                    async move { Self::run_mains(bot, api_tx) }
+                    let account = default_account.clone();
+                    async move { Self::run_mains(bot, &account) }
                 });
             }
 
             let mut drop_task = None;
-            //处理事件，每个事件都会来到这里
+            //处理 Bot 级别的事件
             while let Some(event) = event_rx.recv().await {
-                let api_tx = api_tx.clone();
+                let account = default_account.clone();
                 let bot = bot.clone();
 
                 // Drop为关闭事件，所以要等待，其他的不等待
                 if let InternalEvent::KoviEvent(KoviEvent::Drop) = event {
-                    drop_task = Some(tokio::spawn(Self::handler_event(bot, event, api_tx)));
+                    drop_task = Some(tokio::spawn(Self::handler_event(bot, event, account)));
                     break;
                 } else {
-                    tokio::spawn(Self::handler_event(bot, event, api_tx));
+                    tokio::spawn(Self::handler_event(bot, event, account));
                 }
             }
             if let Some(drop_task) = drop_task {
@@ -111,17 +160,122 @@ impl Bot {
         });
     }
 
+    /// 处理单个账号的事件，每个事件都会来到这里。
+    ///
+    /// 账号的连接失效后只移除此账号，所有账号都失效后关闭 Bot
+    async fn account_event_loop(
+        bot: Arc<RwLock<Self>>,
+        account: Arc<BotAccount>,
+        mut account_event_rx: queue::Receiver<InternalEvent>,
+        event_tx: mpsc::Sender<InternalEvent>,
+        remaining: Arc<AtomicUsize>,
+    ) {
+        let heartbeat_monitor = tokio::spawn(Self::heartbeat_monitor(account.clone()));
+
+        while let Some(event) = account_event_rx.recv().await {
+            if let InternalEvent::KoviEvent(KoviEvent::Drop) = event {
+                break;
+            }
+            // 连接状态相关的事件按顺序处理，其他事件在各自的任务中分发
+            if let Some(event) = Self::handle_account_event(&bot, event, &account).await {
+                tokio::spawn(Self::dispatch_event(bot.clone(), event, account.clone()));
+            }
+        }
+
+        heartbeat_monitor.abort();
+        Self::wait_connecting(&account).await;
+        Self::handler_disconnected(&account);
+        bot.write().unwrap().remove_account(&account);
+        warn!(
+            "Bot connection to {}:{} is closed",
+            account.host, account.port
+        );
+
+        if remaining.fetch_sub(1, Ordering::SeqCst) == 1 {
+            let _ = event_tx
+                .send(InternalEvent::KoviEvent(KoviEvent::Drop))
+                .await;
+        }
+    }
+
+    /// 检测心跳，超过心跳间隔的一半时间仍未收到下一次心跳时触发心跳超时
+    async fn heartbeat_monitor(account: Arc<BotAccount>) {
+        let mut heartbeat_rx = account.heartbeat.subscribe();
+        loop {
+            let interval = *heartbeat_rx.borrow_and_update();
+            let interval = match interval {
+                Some(v) => v,
+                // 还没有收到心跳，或者连接已断开
+                None => {
+                    if heartbeat_rx.changed().await.is_err() {
+                        return;
+                    }
+                    continue;
+                }
+            };
+
+            match tokio::time::timeout(interval * 3 / 2, heartbeat_rx.changed()).await {
+                Ok(Ok(_)) => continue,
+                Ok(Err(_)) => return,
+                Err(_) => {}
+            }
+
+            let self_id = account.self_id.load(Ordering::SeqCst);
+            warn!("Bot {self_id} heartbeat timeout, no heartbeat received in {interval:?}");
+            Self::spawn_listeners(
+                &account.listen.load(),
+                |l| &l.heartbeat_timeout,
+                |listen| listen(self_id),
+            );
+
+            // 每次超时只触发一次，等待下一次心跳
+            if heartbeat_rx.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// 记录账号使用的连接，之后可以通过 self_id 选择账号
+    ///
+    /// 返回此 self_id 是否是第一次记录
+    pub(crate) fn register_account(
+        bot: &RwLock<Self>,
+        self_id: i64,
+        account: &Arc<BotAccount>,
+    ) -> bool {
+        // 登录号没有变化时已经记录过，不需要锁住 Bot
+        if account.self_id.swap(self_id, Ordering::SeqCst) == self_id {
+            return false;
+        }
+        if let Some(v) = bot.read().unwrap().accounts.get(&self_id) {
+            if Arc::ptr_eq(v, account) {
+                return false;
+            }
+        }
+        bot.write()
+            .unwrap()
+            .accounts
+            .insert(self_id, account.clone());
+        true
+    }
+
+    fn remove_account(&mut self, account: &Arc<BotAccount>) {
+        let accounts = &mut self.accounts;
+        self.information
+            .self_infos
+            .retain(|self_id, _| match accounts.get(self_id) {
+                Some(v) => !Arc::ptr_eq(v, account),
+                None => true,
+            });
+        accounts.retain(|_, v| !Arc::ptr_eq(v, account));
+    }
+
     // 运行所有main()
-    fn run_mains(bot: Arc<RwLock<Self>>, api_tx: mpsc::Sender<ApiAndOneshot>) {
+    fn run_mains(bot: Arc<RwLock<Self>>, account: &BotAccount) {
         let bot_ = bot.read().unwrap();
         let main_job_map = bot_.plugins.borrow();
-
-        let (host, port) = {
-            (
-                bot_.information.server.host.clone(),
-                bot_.information.server.port,
-            )
-        };
+        let api_timeout = bot_.information.api_timeout;
+        let retry = bot_.information.retry.clone();
 
         for (name, plugins) in main_job_map.iter() {
             if !plugins.enable_on_startup {
@@ -130,9 +284,11 @@ impl Bot {
             let plugin_builder = PluginBuilder::new(
                 name.clone(),
                 bot.clone(),
-                host.clone(),
-                port,
-                api_tx.clone(),
+                account.host.clone(),
+                account.port,
+                account.api_tx.clone(),
+                api_timeout,
+                retry.clone(),
             );
             Self::run_plugin_main(plugins, plugin_builder);
         }
@@ -256,3 +412,91 @@ pub(crate) async fn exit_signal_check(tx: Sender<InternalEvent>) {
 async fn handler_second_time_exit_signal() {
     exit(1)
 }
+
+#[tokio::test]
+async fn disconnect_then_connect_in_order() {
+    use super::{ApiReturn, Host, KoviConf};
+    use serde_json::json;
+    use std::time::Duration;
+
+    let server = Server::new(
+        Host::Domain("localhost".to_string()),
+        0,
+        String::new(),
+        false,
+    );
+    let mut bot = Bot::build(KoviConf::new(0, None, server, false));
+    bot.mount_main("test", "0.0.0", Arc::new(|| Box::pin(async {})));
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let plugin = bot.plugins.get_mut("test").unwrap();
+    let tx_ = tx.clone();
+    plugin.listen.connected.push(Arc::new(move |_| {
+        let tx = tx_.clone();
+        Box::pin(async move { tx.send("connected").unwrap() })
+    }));
+    plugin.listen.disconnected.push(Arc::new(move |_| {
+        let tx = tx.clone();
+        Box::pin(async move { tx.send("disconnected").unwrap() })
+    }));
+    bot.publish_listen();
+
+    let (api_tx, mut api_rx) = queue::channel::<ApiAndOneshot>("api", &Default::default());
+    let (account_event_tx, account_event_rx) = queue::channel("event", &Default::default());
+    let account = Arc::new(BotAccount::new(
+        Host::Domain("localhost".to_string()),
+        0,
+        api_tx,
+        account_event_tx.monitor(),
+        bot.listen.clone(),
+    ));
+    let bot = Arc::new(RwLock::new(bot));
+
+    // 回复 get_login_info
+    tokio::spawn(async move {
+        while let Some((api, return_tx)) = api_rx.recv().await {
+            let r: ApiReturn = serde_json::from_value(json!({
+                "status": "ok",
+                "retcode": 0,
+                "data": { "user_id": 10001, "nickname": "kovi" },
+                "echo": api.echo,
+            }))
+            .unwrap();
+            if let Some(return_tx) = return_tx {
+                let _ = return_tx.send(Ok(r));
+            }
+        }
+    });
+
+    // 已经连接的账号断开，随后重新连接
+    account.online.store(true, Ordering::SeqCst);
+    account_event_tx
+        .force_send(InternalEvent::KoviEvent(KoviEvent::Disconnected))
+        .unwrap();
+    account_event_tx
+        .push(InternalEvent::OneBotEvent(json!({
+            "time": 1,
+            "self_id": 10001,
+            "post_type": "meta_event",
+            "meta_event_type": "lifecycle",
+            "sub_type": "connect",
+        })))
+        .unwrap();
+
+    let (event_tx, _event_rx) = mpsc::channel(1);
+    let event_loop = tokio::spawn(Bot::account_event_loop(
+        bot,
+        account.clone(),
+        account_event_rx,
+        event_tx,
+        Arc::new(AtomicUsize::new(1)),
+    ));
+
+    let mut order = Vec::new();
+    for _ in 0..2 {
+        let v = tokio::time::timeout(Duration::from_secs(1), rx.recv()).await;
+        order.push(v.unwrap().unwrap());
+    }
+    assert_eq!(order, ["disconnected", "connected"]);
+    assert!(account.online.load(Ordering::SeqCst));
+    event_loop.abort();
+}
@@ -1,8 +1,12 @@
-use super::{ApiAndOneshot, ApiReturn, Bot, Host, SendApi};
-use log::error;
+use super::{
+    queue, rate_limit::ApiClass, ApiAndOneshot, ApiReturn, Bot, Host, RetryPolicy, SendApi,
+};
+use crate::error::{ApiError, QueueError};
+use log::debug;
 use rand::Rng;
-use std::sync::{RwLock, Weak};
-use tokio::sync::{mpsc, oneshot};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::Duration;
+use tokio::sync::oneshot;
 
 pub mod kovi_api;
 pub mod onebot_api;
@@ -24,7 +28,128 @@ pub struct RuntimeBot {
 
     pub(crate) bot: Weak<RwLock<Bot>>,
     pub(crate) plugin_name: String,
-    pub api_tx: mpsc::Sender<ApiAndOneshot>,
+    pub api_tx: queue::Sender<ApiAndOneshot>,
+    /// 等待 api 返回值的默认超时时间
+    pub(crate) api_timeout: Option<Duration>,
+    /// api 失败时的重试策略
+    pub(crate) retry: Arc<RetryPolicy>,
+}
+
+impl RuntimeBot {
+    /// 发送 api 并等待返回值，使用默认的超时时间，失败时按照重试策略重试。
+    ///
+    /// 第一次发送在调用时就放入队列，与不重试时的顺序一致。
+    pub(crate) fn send_api_with_response(
+        &self,
+        send_api: SendApi,
+    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
+        self.send_api_with_response_timeout(send_api, self.api_timeout)
+    }
+
+    /// 与 `send_api_with_response` 相同，但使用 `timeout` 代替默认的超时时间，每次重试都使用此超时时间
+    pub(crate) fn send_api_with_response_timeout(
+        &self,
+        send_api: SendApi,
+        timeout: Option<Duration>,
+    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
+        let retry = (self.retry.max_attempts > 1)
+            .then(|| (self.retry.clone(), self.api_tx.clone(), send_api.clone()));
+        // 不重试时只需要记录 action，不复制整个 api
+        let action = send_api.action.clone();
+
+        let api_rx = send_api_request(&self.api_tx, send_api);
+        let first = send_api_await_response_timeout(api_rx, timeout);
+
+        async move {
+            let r = first.await;
+            match retry {
+                Some((policy, api_tx, send_api)) => {
+                    retry_api(&policy, &api_tx, send_api, timeout, r).await
+                }
+                None => {
+                    match &r {
+                        Ok(_) => debug!("Api {action} succeeded on attempt 1/1"),
+                        Err(e) => debug!("Api {action} failed on attempt 1/1: {e}"),
+                    }
+                    r
+                }
+            }
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 第 `attempt` 次失败后，重试前等待的时间
+    fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .backoff
+            .saturating_mul(1 << (attempt - 1).min(16))
+            .min(self.max_backoff);
+        Duration::from_millis(backoff)
+    }
+
+    /// 这个错误是否可以重试，只有查询 api 是幂等的，其他 api 只在确定没有发送时重试
+    fn retryable(&self, action: &str, e: &ApiError) -> bool {
+        let idempotent = ApiClass::of(action) == ApiClass::Query;
+        match e {
+            ApiError::QueueFull => true,
+            ApiError::Timeout | ApiError::ConnectionClosed => idempotent && self.retry_timeout,
+            ApiError::Failed { retcode, .. } => idempotent && self.retcodes.contains(retcode),
+            _ => false,
+        }
+    }
+}
+
+/// 按照重试策略重新发送失败的 api，`r` 为第一次发送的结果
+async fn retry_api(
+    policy: &RetryPolicy,
+    api_tx: &queue::Sender<ApiAndOneshot>,
+    mut send_api: SendApi,
+    timeout: Option<Duration>,
+    mut r: Result<ApiReturn, ApiError>,
+) -> Result<ApiReturn, ApiError> {
+    let max = policy.max_attempts;
+    let mut attempt = 1;
+    loop {
+        let e = match &r {
+            Ok(_) => {
+                debug!(
+                    "Api {} succeeded on attempt {attempt}/{max}",
+                    send_api.action
+                );
+                return r;
+            }
+            Err(e) => e,
+        };
+
+        if !policy.retryable(&send_api.action, e) {
+            debug!(
+                "Api {} failed on attempt {attempt}/{max}, not retryable: {e}",
+                send_api.action
+            );
+            return r;
+        }
+        if attempt >= max {
+            debug!(
+                "Api {} failed on attempt {attempt}/{max}, giving up: {e}",
+                send_api.action
+            );
+            return r;
+        }
+
+        let delay = policy.delay(attempt);
+        debug!(
+            "Api {} failed on attempt {attempt}/{max}, retrying in {delay:?}: {e}",
+            send_api.action
+        );
+        tokio::time::sleep(delay).await;
+
+        attempt += 1;
+        // 使用新的 echo，避免上一次迟到的返回值被当作这一次的
+        send_api.echo = rand_echo();
+        let api_rx = send_api_request(api_tx, send_api.clone());
+        r = send_api_await_response_timeout(api_rx, timeout).await;
+    }
 }
 
 pub fn rand_echo() -> String {
@@ -37,68 +162,140 @@ pub fn rand_echo() -> String {
     s
 }
 
-type ApiOneshotSender = oneshot::Sender<Result<ApiReturn, ApiReturn>>;
-type ApiOneshotReceiver = oneshot::Receiver<Result<ApiReturn, ApiReturn>>;
+type ApiOneshotSender = oneshot::Sender<Result<ApiReturn, ApiError>>;
+type ApiOneshotReceiver = oneshot::Receiver<Result<ApiReturn, ApiError>>;
 
 pub fn send_api_request_with_response(
-    api_tx: &mpsc::Sender<ApiAndOneshot>,
+    api_tx: &queue::Sender<ApiAndOneshot>,
     send_api: SendApi,
-) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
     let api_rx = send_api_request(api_tx, send_api);
     send_api_await_response(api_rx)
 }
 
 pub fn send_api_request(
-    api_tx: &mpsc::Sender<ApiAndOneshot>,
+    api_tx: &queue::Sender<ApiAndOneshot>,
     send_api: SendApi,
 ) -> ApiOneshotReceiver {
     let (api_tx_, api_rx): (ApiOneshotSender, ApiOneshotReceiver) = oneshot::channel();
 
-    if let Err(e) = api_tx.try_send((send_api, Some(api_tx_))) {
-        match e {
-            mpsc::error::TrySendError::Full(v) => {
-                log::trace!("RuntimeBot Api Queue Full, spawn new task to send");
+    push_api(api_tx, (send_api, Some(api_tx_)));
 
-                let api_tx = api_tx.clone();
+    api_rx
+}
 
-                tokio::task::spawn(async move {
-                    api_tx.send(v).await.unwrap();
-                });
-            }
-            mpsc::error::TrySendError::Closed(_) => {
-                log::error!("RuntimeBot Api Queue Closed");
-            }
+pub fn send_api_request_with_forget(api_tx: &queue::Sender<ApiAndOneshot>, send_api: SendApi) {
+    push_api(api_tx, (send_api, None));
+}
+
+/// 按顺序放入 api 队列。因队列已满被丢弃或拒绝的 api 返回 `ApiError::QueueFull`
+fn push_api(api_tx: &queue::Sender<ApiAndOneshot>, api: ApiAndOneshot) {
+    let full = match api_tx.push(api) {
+        Ok(None) => return,
+        Ok(Some(v)) | Err(QueueError::Full(v)) => v,
+        Err(QueueError::Closed(_)) => {
+            log::error!("RuntimeBot Api Queue Closed");
+            return;
         }
     };
 
-    api_rx
+    if let (_, Some(return_tx)) = full {
+        let _ = return_tx.send(Err(ApiError::QueueFull));
+    }
 }
 
-pub fn send_api_request_with_forget(api_tx: &mpsc::Sender<ApiAndOneshot>, send_api: SendApi) {
-    if let Err(e) = api_tx.try_send((send_api, None)) {
-        match e {
-            mpsc::error::TrySendError::Full(v) => {
-                log::trace!("RuntimeBot Api Queue Full, spawn new task to send");
+/// 等待 api 返回值，不会超时
+pub async fn send_api_await_response(api_rx: ApiOneshotReceiver) -> Result<ApiReturn, ApiError> {
+    send_api_await_response_timeout(api_rx, None).await
+}
 
-                let api_tx = api_tx.clone();
+/// 等待 api 返回值，`timeout` 为 `None` 时不会超时
+pub async fn send_api_await_response_timeout(
+    api_rx: ApiOneshotReceiver,
+    timeout: Option<Duration>,
+) -> Result<ApiReturn, ApiError> {
+    let r = match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, api_rx).await {
+            Ok(v) => v,
+            Err(_) => return Err(ApiError::Timeout),
+        },
+        None => api_rx.await,
+    };
 
-                tokio::task::spawn(async move {
-                    api_tx.send(v).await.unwrap();
-                });
-            }
-            mpsc::error::TrySendError::Closed(_) => {
-                log::error!("RuntimeBot Api Queue Closed");
-            }
+    match r {
+        Ok(v) => v,
+        // api 在发送前被丢弃
+        Err(_) => Err(ApiError::Cancelled),
+    }
+}
+
+#[tokio::test]
+async fn api_retry() {
+    use serde_json::json;
+
+    let (api_tx, mut api_rx) = queue::channel::<ApiAndOneshot>("api", &Default::default());
+    // 前两次返回 1200，之后成功
+    tokio::spawn(async move {
+        let mut count = 0;
+        while let Some((api, return_tx)) = api_rx.recv().await {
+            count += 1;
+            let retcode = if count <= 2 || api.action == "send_msg" {
+                1200
+            } else {
+                0
+            };
+            let r = ApiReturn {
+                status: String::from(if retcode == 0 { "ok" } else { "failed" }),
+                retcode,
+                data: json!(count),
+                echo: api.echo,
+                message: String::new(),
+                wording: String::new(),
+            };
+            let r = if retcode == 0 { Ok(r) } else { Err(r.into()) };
+            let _ = return_tx.unwrap().send(r);
+        }
+    });
+
+    let policy = RetryPolicy {
+        max_attempts: 3,
+        backoff: 1,
+        ..Default::default()
+    };
+    let send = |action: &str, policy: &RetryPolicy| {
+        let send_api = SendApi::new(action, json!({}), &rand_echo());
+        let api_rx = send_api_request(&api_tx, send_api.clone());
+        let policy = policy.clone();
+        let api_tx = api_tx.clone();
+        async move {
+            let r = send_api_await_response_timeout(api_rx, None).await;
+            retry_api(&policy, &api_tx, send_api, None, r).await
         }
     };
+
+    let r = send("get_group_info", &policy).await.unwrap();
+    assert_eq!(r.data, json!(3));
+
+    // 发送消息不会因为 retcode 重试
+    let r = send("send_msg", &policy).await;
+    assert!(matches!(r, Err(ApiError::Failed { retcode: 1200, .. })));
+    assert!(policy.retryable("send_msg", &ApiError::QueueFull));
+    assert!(!policy.retryable("send_msg", &ApiError::Timeout));
+    // 不在查询白名单中的 api 都视为不幂等
+    assert!(policy.retryable("can_send_image", &ApiError::Timeout));
+    assert!(!policy.retryable("set_group_kick", &ApiError::Timeout));
+    assert!(!policy.retryable("delete_msg", &ApiError::ConnectionClosed));
+    assert_eq!(policy.delay(3), Duration::from_millis(4));
 }
 
-pub async fn send_api_await_response(api_rx: ApiOneshotReceiver) -> Result<ApiReturn, ApiReturn> {
-    match api_rx.await {
-        Ok(v) => v,
-        Err(e) => {
-            error!("{e}");
-            panic!()
-        }
-    }
+#[tokio::test]
+async fn api_response_timeout() {
+    let (_api_tx, api_rx): (ApiOneshotSender, ApiOneshotReceiver) = oneshot::channel();
+    let r = send_api_await_response_timeout(api_rx, Some(Duration::from_millis(10))).await;
+    assert!(matches!(r, Err(ApiError::Timeout)));
+
+    let (api_tx, api_rx): (ApiOneshotSender, ApiOneshotReceiver) = oneshot::channel();
+    drop(api_tx);
+    let r = send_api_await_response(api_rx).await;
+    assert!(matches!(r, Err(ApiError::Cancelled)));
 }
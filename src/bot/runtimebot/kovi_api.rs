@@ -1,6 +1,8 @@
+use super::onebot_api::LoginInfo;
 use super::RuntimeBot;
 use crate::{
-    bot::{ApiAndOneshot, PluginInfo},
+    bot::queue::{Priority, QueueStats},
+    bot::{PluginInfo, RetryPolicy},
     error::BotError,
     Bot, PluginBuilder,
 };
@@ -10,7 +12,6 @@ use std::{
     path::PathBuf,
     sync::{Arc, RwLock},
 };
-use tokio::sync::mpsc;
 
 #[deprecated(since = "0.11.0", note = "弃用，直接删掉就好了")]
 pub trait KoviApi {}
@@ -83,6 +84,8 @@ impl RuntimeBot {
 
         plugin.access_control = enable;
 
+        bot.publish_listen();
+
         Ok(())
     }
 
@@ -115,6 +118,8 @@ impl RuntimeBot {
 
         plugin.list_mode = access_control_mode;
 
+        bot.publish_listen();
+
         Ok(())
     }
 
@@ -195,6 +200,8 @@ impl RuntimeBot {
             }
         }
 
+        bot.publish_listen();
+
         Ok(())
     }
 }
@@ -232,6 +239,8 @@ impl RuntimeBot {
             }
         }
 
+        bot.publish_listen();
+
         Ok(())
     }
 
@@ -289,6 +298,22 @@ impl RuntimeBot {
 
         Ok(admins)
     }
+
+    /// 获取配置的媒体大小限制，单位字节，0 为不限制。用于 `Message` 的 `_with_limit` 方法
+    ///
+    /// # Error
+    ///
+    /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回 `BotError::RefExpired` 错误。
+    /// 这通常出现在Bot已经关闭，可有个不受Kovi管理的线程仍然拥有此 RuntimeBot。
+    pub fn get_media_size_limit(&self) -> Result<u64, BotError> {
+        let bot = match self.bot.upgrade() {
+            Some(b) => b,
+            None => return Err(BotError::RefExpired),
+        };
+
+        let limit = bot.read().unwrap().information.media_size_limit;
+        Ok(limit)
+    }
 }
 
 /// 工具
@@ -384,10 +409,14 @@ impl RuntimeBot {
 
     /// 启用传入的插件
     ///
+    /// 插件使用 Bot 的默认配置与默认账号启动，不受此实例的 `with_priority`、`with_retry`、`with_account` 影响
+    ///
     /// # error
     ///
     /// 如果寻找不到插件，会返回Err `BotError::PluginNotFound`
     ///
+    /// 如果 Bot 还没有账号连接成功，会返回Err `BotError::NotLoggedIn`
+    ///
     /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回Err `BotError::RefExpired` 。
     /// 这通常出现在 `Bot` 已经关闭，可有个不受 Kovi 管理的线程仍然拥有此 `RuntimeBot`。
     pub fn enable_plugin<T: AsRef<str>>(&self, plugin_name: T) -> Result<(), BotError> {
@@ -400,7 +429,7 @@ impl RuntimeBot {
             None => return Err(BotError::RefExpired),
         };
 
-        enable_plugin(bot, plugin_name, self.api_tx.clone())
+        enable_plugin(bot, plugin_name)
     }
 
     /// 插件是否开启
@@ -429,6 +458,164 @@ impl RuntimeBot {
     }
 }
 
+/// 多账号
+impl RuntimeBot {
+    /// 获取使用指定账号的 `RuntimeBot`，通过它调用的 api 都会发送到此账号的连接。
+    ///
+    /// 账号需要已经连接，并且收到过此账号的事件。
+    ///
+    /// # Error
+    ///
+    /// 如果没有此账号，会返回Err `BotError::AccountNotFound`
+    ///
+    /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回 `BotError::RefExpired` 错误。
+    /// 这通常出现在Bot已经关闭，可有个不受Kovi管理的线程仍然拥有此 RuntimeBot。
+    pub fn with_account(&self, self_id: i64) -> Result<RuntimeBot, BotError> {
+        let bot = match self.bot.upgrade() {
+            Some(b) => b,
+            None => return Err(BotError::RefExpired),
+        };
+
+        let bot = bot.read().unwrap();
+        let account = match bot.accounts.get(&self_id) {
+            Some(v) => v,
+            None => return Err(BotError::AccountNotFound(self_id)),
+        };
+
+        Ok(RuntimeBot {
+            host: account.host.clone(),
+            port: account.port,
+            bot: self.bot.clone(),
+            plugin_name: self.plugin_name.clone(),
+            api_tx: account.api_tx.clone(),
+            api_timeout: self.api_timeout,
+            retry: self.retry.clone(),
+        })
+    }
+
+    /// 获取一个以 `priority` 发送 api 的 RuntimeBot，与此实例使用同一个账号。
+    ///
+    /// 高优先级的 api 会先于队列中其他的 api 发送，适合禁言、撤回等处理违规的操作；
+    /// 群发等批量操作可以使用 `Priority::Low`，避免影响其他插件。
+    ///
+    /// 同一优先级中，不同插件的 api 轮流发送。
+    pub fn with_priority(&self, priority: Priority) -> RuntimeBot {
+        RuntimeBot {
+            api_tx: self.api_tx.with_priority(priority),
+            ..self.clone()
+        }
+    }
+
+    /// 当前发送 api 使用的优先级
+    pub fn get_priority(&self) -> Priority {
+        self.api_tx.priority()
+    }
+
+    /// 获取一个使用 `retry` 重试策略的 RuntimeBot，与此实例使用同一个账号。
+    ///
+    /// 只影响等待返回值的 api，`get_` 与 `can_` 开头以外的 api 只在确定没有发送时重试，不会重复执行。
+    pub fn with_retry(&self, retry: RetryPolicy) -> RuntimeBot {
+        RuntimeBot {
+            retry: Arc::new(retry),
+            ..self.clone()
+        }
+    }
+
+    /// 当前使用的重试策略
+    pub fn get_retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    /// 获取所有已连接账号的 self_id
+    ///
+    /// # Error
+    ///
+    /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回 `BotError::RefExpired` 错误。
+    /// 这通常出现在Bot已经关闭，可有个不受Kovi管理的线程仍然拥有此 RuntimeBot。
+    pub fn get_accounts(&self) -> Result<Vec<i64>, BotError> {
+        let bot = match self.bot.upgrade() {
+            Some(b) => b,
+            None => return Err(BotError::RefExpired),
+        };
+
+        let ids = bot.read().unwrap().accounts.keys().cloned().collect();
+        Ok(ids)
+    }
+
+    /// 获取此 `RuntimeBot` 所使用账号的 self_id
+    ///
+    /// # Error
+    ///
+    /// 如果账号还没有收到过事件，将会返回 `BotError::NotLoggedIn` 错误。
+    ///
+    /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回 `BotError::RefExpired` 错误。
+    /// 这通常出现在Bot已经关闭，可有个不受Kovi管理的线程仍然拥有此 RuntimeBot。
+    pub fn get_self_id(&self) -> Result<i64, BotError> {
+        let bot = match self.bot.upgrade() {
+            Some(b) => b,
+            None => return Err(BotError::RefExpired),
+        };
+
+        let bot = bot.read().unwrap();
+        bot.accounts
+            .iter()
+            .find(|(_, account)| account.api_tx.same_channel(&self.api_tx))
+            .map(|(self_id, _)| *self_id)
+            .ok_or(BotError::NotLoggedIn)
+    }
+
+    /// 获取此 `RuntimeBot` 所使用账号的登录信息，每次 lifecycle 事件时刷新
+    ///
+    /// # Error
+    ///
+    /// 如果还没有获取到登录信息，将会返回 `BotError::NotLoggedIn` 错误。
+    ///
+    /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回 `BotError::RefExpired` 错误。
+    /// 这通常出现在Bot已经关闭，可有个不受Kovi管理的线程仍然拥有此 RuntimeBot。
+    pub fn get_self_info(&self) -> Result<LoginInfo, BotError> {
+        let self_id = self.get_self_id()?;
+
+        let bot = match self.bot.upgrade() {
+            Some(b) => b,
+            None => return Err(BotError::RefExpired),
+        };
+
+        let bot = bot.read().unwrap();
+        bot.information
+            .self_infos
+            .get(&self_id)
+            .cloned()
+            .ok_or(BotError::NotLoggedIn)
+    }
+
+    /// 获取此 `RuntimeBot` 所使用账号的 api 队列状态
+    pub fn get_api_queue_stats(&self) -> QueueStats {
+        self.api_tx.stats()
+    }
+
+    /// 获取此 `RuntimeBot` 所使用账号的事件队列状态
+    ///
+    /// # Error
+    ///
+    /// 如果账号还没有收到过事件，将会返回 `BotError::NotLoggedIn` 错误。
+    ///
+    /// 如果此 `RuntimeBot` 实例内部的 `Bot` 中已经不存在，将会返回 `BotError::RefExpired` 错误。
+    /// 这通常出现在Bot已经关闭，可有个不受Kovi管理的线程仍然拥有此 RuntimeBot。
+    pub fn get_event_queue_stats(&self) -> Result<QueueStats, BotError> {
+        let bot = match self.bot.upgrade() {
+            Some(b) => b,
+            None => return Err(BotError::RefExpired),
+        };
+
+        let bot = bot.read().unwrap();
+        bot.accounts
+            .values()
+            .find(|account| account.api_tx.same_channel(&self.api_tx))
+            .map(|account| account.event_queue.stats())
+            .ok_or(BotError::NotLoggedIn)
+    }
+}
+
 pub(crate) fn disable_plugin<T: AsRef<str>>(
     bot: Arc<RwLock<Bot>>,
     plugin_name: T,
@@ -444,39 +631,41 @@ pub(crate) fn disable_plugin<T: AsRef<str>>(
             None => return Err(BotError::PluginNotFound(plugin_name.to_string())),
         };
         join = bot_plugin.shutdown();
+        bot.publish_listen();
     }
 
     Ok(join)
 }
 
-fn enable_plugin<T: AsRef<str>>(
-    bot: Arc<RwLock<Bot>>,
-    plugin_name: T,
-    api_tx: mpsc::Sender<ApiAndOneshot>,
-) -> Result<(), BotError> {
+fn enable_plugin<T: AsRef<str>>(bot: Arc<RwLock<Bot>>, plugin_name: T) -> Result<(), BotError> {
     let bot_read = bot.read().unwrap();
     let plugin_name = plugin_name.as_ref();
 
-    let (host, port) = {
-        (
-            bot_read.information.server.host.clone(),
-            bot_read.information.server.port,
-        )
-    };
-
     let bot_plugin = match bot_read.plugins.get(plugin_name) {
         Some(v) => v,
         None => return Err(BotError::PluginNotFound(plugin_name.to_string())),
     };
 
+    let account = match &bot_read.default_account {
+        Some(v) => v,
+        None => return Err(BotError::NotLoggedIn),
+    };
+
     bot_plugin.enabled.send_modify(|v| {
         *v = true;
     });
 
     let plugin_ = bot_plugin.clone();
 
-    let plugin_builder =
-        PluginBuilder::new(plugin_name.to_string(), bot.clone(), host, port, api_tx);
+    let plugin_builder = PluginBuilder::new(
+        plugin_name.to_string(),
+        bot.clone(),
+        account.host.clone(),
+        account.port,
+        account.api_tx.clone(),
+        bot_read.information.api_timeout,
+        bot_read.information.retry.clone(),
+    );
 
     tokio::spawn(async move { Bot::run_plugin_main(&plugin_, plugin_builder) });
 
@@ -1,16 +1,24 @@
-use super::{
-    send_api_await_response, send_api_request, send_api_request_with_forget,
-    send_api_request_with_response, RuntimeBot,
-};
+use super::{send_api_request_with_forget, RuntimeBot};
 use crate::bot::ApiReturn;
-use crate::bot::{message::Message, runtimebot::rand_echo, SendApi};
+use crate::bot::{
+    message::{ForwardMessage, Message},
+    runtimebot::rand_echo,
+    SendApi,
+};
+use crate::error::ApiError;
 use log::info;
+use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json::{json, Value};
+use std::time::Duration;
 
 #[cfg(feature = "cqstring")]
 use crate::bot::message::CQMessage;
 
+mod types;
+
+pub use types::*;
+
 pub enum HonorType {
     All,
     Talkative,
@@ -25,6 +33,32 @@ pub enum AddRequestType<'a> {
     SubType(&'a str),
 }
 
+/// 取出返回值 `data` 中的字段，字段不存在或类型不符时返回 `ApiError::ParseError`
+fn return_field<T>(
+    raw: ApiReturn,
+    key: &str,
+    f: impl FnOnce(&Value) -> Option<T>,
+) -> Result<T, ApiError> {
+    match raw.data.get(key).and_then(f) {
+        Some(v) => Ok(v),
+        None => Err(ApiError::ParseError {
+            message: format!("missing or invalid field `{key}` in data"),
+            raw: Box::new(raw),
+        }),
+    }
+}
+
+/// 将返回值的 `data` 解析为 `T`，结构不符时返回 `ApiError::ParseError`
+pub(crate) fn parse_data<T: DeserializeOwned>(raw: ApiReturn) -> Result<T, ApiError> {
+    match T::deserialize(&raw.data) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(ApiError::ParseError {
+            message: e.to_string(),
+            raw: Box::new(raw),
+        }),
+    }
+}
+
 /// Kovi提供解析过的返回值的api
 impl RuntimeBot {
     ///发送群组消息, 并返回消息ID
@@ -33,7 +67,7 @@ impl RuntimeBot {
         &self,
         group_id: i64,
         msg: T,
-    ) -> impl std::future::Future<Output = Result<i32, ApiReturn>>
+    ) -> impl std::future::Future<Output = Result<i32, ApiError>>
     where
         Message: From<T>,
         T: Serialize,
@@ -54,17 +88,9 @@ impl RuntimeBot {
 
         info!("[send] [to group {group_id}]: {}", msg.to_human_string());
 
-        let api_rx = send_api_request(&self.api_tx, send_api);
-
-        async move {
-            let r = send_api_await_response(api_rx).await;
-
-            match r {
-                Ok(v) => Ok(v.data.get("message_id").unwrap().as_i64().unwrap() as i32),
+        let response = self.send_api_with_response(send_api);
 
-                Err(v) => Err(v),
-            }
-        }
+        async move { return_field(response.await?, "message_id", Value::as_i64).map(|id| id as i32) }
     }
 
     ///发送群组消息, 并返回消息ID
@@ -73,7 +99,7 @@ impl RuntimeBot {
         &self,
         group_id: i64,
         msg: T,
-    ) -> impl std::future::Future<Output = Result<i32, ApiReturn>>
+    ) -> impl std::future::Future<Output = Result<i32, ApiError>>
     where
         CQMessage: From<T>,
         T: Serialize,
@@ -96,16 +122,9 @@ impl RuntimeBot {
             Message::from(msg).to_human_string()
         );
 
-        let api_rx = send_api_request(&self.api_tx, send_api);
+        let response = self.send_api_with_response(send_api);
 
-        async move {
-            let r = send_api_await_response(api_rx).await;
-            match r {
-                Ok(v) => Ok(v.data.get("message_id").unwrap().as_i64().unwrap() as i32),
-
-                Err(v) => Err(v),
-            }
-        }
+        async move { return_field(response.await?, "message_id", Value::as_i64).map(|id| id as i32) }
     }
 
     #[cfg(not(feature = "cqstring"))]
@@ -114,7 +133,7 @@ impl RuntimeBot {
         &self,
         user_id: i64,
         msg: T,
-    ) -> impl std::future::Future<Output = Result<i32, ApiReturn>>
+    ) -> impl std::future::Future<Output = Result<i32, ApiError>>
     where
         Message: From<T>,
         T: Serialize,
@@ -132,17 +151,9 @@ impl RuntimeBot {
         let user_id = &user_id;
         info!("[send] [to private {user_id}]: {}", msg.to_human_string());
 
-        let api_rx = send_api_request(&self.api_tx, send_api);
-
-        async move {
-            let r = send_api_await_response(api_rx).await;
+        let response = self.send_api_with_response(send_api);
 
-            match r {
-                Ok(v) => Ok(v.data.get("message_id").unwrap().as_i64().unwrap() as i32),
-
-                Err(v) => Err(v),
-            }
-        }
+        async move { return_field(response.await?, "message_id", Value::as_i64).map(|id| id as i32) }
     }
 
     #[cfg(feature = "cqstring")]
@@ -151,7 +162,7 @@ impl RuntimeBot {
         &self,
         user_id: i64,
         msg: T,
-    ) -> impl std::future::Future<Output = Result<i32, ApiReturn>>
+    ) -> impl std::future::Future<Output = Result<i32, ApiError>>
     where
         CQMessage: From<T>,
         T: Serialize,
@@ -172,48 +183,71 @@ impl RuntimeBot {
             Message::from(msg).to_human_string()
         );
 
-        let api_rx = send_api_request(&self.api_tx, send_api);
+        let response = self.send_api_with_response(send_api);
+
+        async move { return_field(response.await?, "message_id", Value::as_i64).map(|id| id as i32) }
+    }
+
+    /// 发送群合并转发消息，返回消息 ID 与合并转发 ID
+    pub fn send_group_forward_msg(
+        &self,
+        group_id: i64,
+        msg: ForwardMessage,
+    ) -> impl std::future::Future<Output = Result<ForwardMsgReturn, ApiError>> {
+        info!("[send] [to group {group_id}]: [forward]");
+
+        let send_api = SendApi::new(
+            "send_group_forward_msg",
+            json!({
+                "group_id":group_id,
+                "messages":msg,
+            }),
+            &rand_echo(),
+        );
+
+        let response = self.send_api_with_response(send_api);
+
+        async move { parse_data(response.await?) }
+    }
+
+    /// 发送私聊合并转发消息，返回消息 ID 与合并转发 ID
+    pub fn send_private_forward_msg(
+        &self,
+        user_id: i64,
+        msg: ForwardMessage,
+    ) -> impl std::future::Future<Output = Result<ForwardMsgReturn, ApiError>> {
+        info!("[send] [to private {user_id}]: [forward]");
+
+        let send_api = SendApi::new(
+            "send_private_forward_msg",
+            json!({
+                "user_id":user_id,
+                "messages":msg,
+            }),
+            &rand_echo(),
+        );
 
-        async move {
-            let r = send_api_await_response(api_rx).await;
-            match r {
-                Ok(v) => Ok(v.data.get("message_id").unwrap().as_i64().unwrap() as i32),
+        let response = self.send_api_with_response(send_api);
 
-                Err(v) => Err(v),
-            }
-        }
+        async move { parse_data(response.await?) }
     }
 
     /// 是否能发送图片
-    pub fn can_send_image(&self) -> impl std::future::Future<Output = Result<bool, ApiReturn>> {
+    pub fn can_send_image(&self) -> impl std::future::Future<Output = Result<bool, ApiError>> {
         let send_api = SendApi::new("can_send_image", json!({}), &rand_echo());
 
-        let api_rx = send_api_request(&self.api_tx, send_api);
+        let response = self.send_api_with_response(send_api);
 
-        async move {
-            let r = send_api_await_response(api_rx).await;
-            match r {
-                Ok(v) => Ok(v.data.get("yes").unwrap().as_bool().unwrap()),
-
-                Err(v) => Err(v),
-            }
-        }
+        async move { return_field(response.await?, "yes", Value::as_bool) }
     }
 
     /// 是否能发送语音
-    pub fn can_send_record(&self) -> impl std::future::Future<Output = Result<bool, ApiReturn>> {
+    pub fn can_send_record(&self) -> impl std::future::Future<Output = Result<bool, ApiError>> {
         let send_api = SendApi::new("can_send_record", json!({}), &rand_echo());
 
-        let api_rx = send_api_request(&self.api_tx, send_api);
-
-        async move {
-            let r = send_api_await_response(api_rx).await;
-            match r {
-                Ok(v) => Ok(v.data.get("yes").unwrap().as_bool().unwrap()),
+        let response = self.send_api_with_response(send_api);
 
-                Err(v) => Err(v),
-            }
-        }
+        async move { return_field(response.await?, "yes", Value::as_bool) }
     }
 }
 
@@ -672,7 +706,7 @@ impl RuntimeBot {
     pub fn get_msg(
         &self,
         message_id: i32,
-    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let send_api = SendApi::new(
             "get_msg",
             json!({
@@ -681,7 +715,7 @@ impl RuntimeBot {
             &rand_echo(),
         );
 
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
     }
     /// 获取合并转发消息
     /// # Arguments
@@ -690,7 +724,7 @@ impl RuntimeBot {
     pub fn get_forward_msg(
         &self,
         id: &str,
-    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let send_api = SendApi::new(
             "get_forward_msg",
             json!({
@@ -699,15 +733,13 @@ impl RuntimeBot {
             &rand_echo(),
         );
 
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
     }
     /// 获取获取登录号信息
-    pub fn get_login_info(
-        &self,
-    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    pub fn get_login_info(&self) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let send_api = SendApi::new("get_login_info", json!({}), &rand_echo());
 
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
     }
     /// 获取获取陌生人信息
     /// # Arguments
@@ -719,7 +751,7 @@ impl RuntimeBot {
         &self,
         user_id: i64,
         no_cache: bool,
-    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let send_api = SendApi::new(
             "get_stranger_info",
             json!({
@@ -729,15 +761,15 @@ impl RuntimeBot {
             &rand_echo(),
         );
 
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
     }
     /// 获取好友列表
     pub fn get_friend_list(
         &self,
-    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let send_api = SendApi::new("get_friend_list", json!({}), &rand_echo());
 
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
     }
     /// 获取群信息
     /// # Arguments
@@ -749,7 +781,7 @@ impl RuntimeBot {
         &self,
         group_id: i64,
         no_cache: bool,
-    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let send_api = SendApi::new(
             "get_group_info",
             json!({
@@ -759,15 +791,13 @@ impl RuntimeBot {
             &rand_echo(),
         );
 
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
     }
     /// 获取群列表
-    pub fn get_group_list(
-        &self,
-    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    pub fn get_group_list(&self) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let send_api = SendApi::new("get_group_list", json!({}), &rand_echo());
 
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
     }
     ///获取群成员信息
     /// # Arguments
@@ -782,7 +812,7 @@ impl RuntimeBot {
         group_id: i64,
         user_id: i64,
         no_cache: bool,
-    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let send_api = SendApi::new(
             "get_group_member_info",
             json!({
@@ -793,7 +823,7 @@ impl RuntimeBot {
             &rand_echo(),
         );
 
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
     }
     /// 获取群成员列表
     ///
@@ -803,7 +833,7 @@ impl RuntimeBot {
     pub fn get_group_member_list(
         &self,
         group_id: i64,
-    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let send_api = SendApi::new(
             "get_group_member_list",
             json!({
@@ -812,7 +842,7 @@ impl RuntimeBot {
             &rand_echo(),
         );
 
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
     }
 
     /// 获取群荣誉信息
@@ -825,7 +855,7 @@ impl RuntimeBot {
         &self,
         group_id: i64,
         honor_type: HonorType,
-    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let honor_type = match honor_type {
             HonorType::All => "all",
             HonorType::Talkative => "talkative",
@@ -844,7 +874,7 @@ impl RuntimeBot {
             &rand_echo(),
         );
 
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
     }
 
     /// 获取相关接口凭证, 即 Cookies 和 CSRF Token 的合并。
@@ -855,7 +885,7 @@ impl RuntimeBot {
     pub fn get_credentials(
         &self,
         domain: &str,
-    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let send_api = SendApi::new(
             "get_credentials",
             json!({
@@ -864,21 +894,21 @@ impl RuntimeBot {
             &rand_echo(),
         );
 
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
     }
 
     /// 获取运行状态
-    pub fn get_status(&self) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    pub fn get_status(&self) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let send_api = SendApi::new("get_status", json!({}), &rand_echo());
 
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
     }
     /// 获取版本信息
     pub fn get_version_info(
         &self,
-    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let send_api = SendApi::new("get_version_info", json!({}), &rand_echo());
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
     }
     /// 获取 Cookies
     ///
@@ -888,7 +918,7 @@ impl RuntimeBot {
     pub fn get_cookies(
         &self,
         domain: &str,
-    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let send_api = SendApi::new(
             "get_cookies",
             json!({
@@ -896,15 +926,13 @@ impl RuntimeBot {
             }),
             &rand_echo(),
         );
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
     }
     /// 获取 CSRF Token
-    pub fn get_csrf_token(
-        &self,
-    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    pub fn get_csrf_token(&self) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let send_api = SendApi::new("get_csrf_token", json!({}), &rand_echo());
 
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
     }
     /// 获取语音
     ///
@@ -917,7 +945,7 @@ impl RuntimeBot {
         &self,
         file: &str,
         out_format: &str,
-    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let send_api = SendApi::new(
             "get_record",
             json!({
@@ -926,7 +954,7 @@ impl RuntimeBot {
             }),
             &rand_echo(),
         );
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
     }
     /// 获取图片
     ///
@@ -936,7 +964,7 @@ impl RuntimeBot {
     pub fn get_image(
         &self,
         file: &str,
-    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let send_api = SendApi::new(
             "get_image",
             json!({
@@ -944,7 +972,7 @@ impl RuntimeBot {
             }),
             &rand_echo(),
         );
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
     }
 
     /// 点赞，有些服务端会返回点赞失败，不关注返回值的话请使用 send_like()
@@ -957,7 +985,7 @@ impl RuntimeBot {
         &self,
         user_id: i64,
         times: usize,
-    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let send_api = SendApi::new(
             "send_like",
             json!({
@@ -967,7 +995,145 @@ impl RuntimeBot {
             &rand_echo(),
         );
 
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
+    }
+}
+
+/// 解析为结构体的返回值，参数与同名不带 `_typed` 的方法相同。
+///
+/// 需要实现拓展的字段时，请使用不带 `_typed` 的方法获取原始的 `ApiReturn`
+impl RuntimeBot {
+    /// 获取登录号信息
+    pub fn get_login_info_typed(
+        &self,
+    ) -> impl std::future::Future<Output = Result<LoginInfo, ApiError>> {
+        let response = self.get_login_info();
+        async move { parse_data(response.await?) }
+    }
+
+    /// 获取陌生人信息
+    pub fn get_stranger_info_typed(
+        &self,
+        user_id: i64,
+        no_cache: bool,
+    ) -> impl std::future::Future<Output = Result<StrangerInfo, ApiError>> {
+        let response = self.get_stranger_info(user_id, no_cache);
+        async move { parse_data(response.await?) }
+    }
+
+    /// 获取好友列表
+    pub fn get_friend_list_typed(
+        &self,
+    ) -> impl std::future::Future<Output = Result<Vec<FriendInfo>, ApiError>> {
+        let response = self.get_friend_list();
+        async move { parse_data(response.await?) }
+    }
+
+    /// 获取群信息
+    pub fn get_group_info_typed(
+        &self,
+        group_id: i64,
+        no_cache: bool,
+    ) -> impl std::future::Future<Output = Result<GroupInfo, ApiError>> {
+        let response = self.get_group_info(group_id, no_cache);
+        async move { parse_data(response.await?) }
+    }
+
+    /// 获取群列表
+    pub fn get_group_list_typed(
+        &self,
+    ) -> impl std::future::Future<Output = Result<Vec<GroupInfo>, ApiError>> {
+        let response = self.get_group_list();
+        async move { parse_data(response.await?) }
+    }
+
+    /// 获取群成员信息
+    pub fn get_group_member_info_typed(
+        &self,
+        group_id: i64,
+        user_id: i64,
+        no_cache: bool,
+    ) -> impl std::future::Future<Output = Result<GroupMemberInfo, ApiError>> {
+        let response = self.get_group_member_info(group_id, user_id, no_cache);
+        async move { parse_data(response.await?) }
+    }
+
+    /// 获取群成员列表
+    pub fn get_group_member_list_typed(
+        &self,
+        group_id: i64,
+    ) -> impl std::future::Future<Output = Result<Vec<GroupMemberInfo>, ApiError>> {
+        let response = self.get_group_member_list(group_id);
+        async move { parse_data(response.await?) }
+    }
+
+    /// 获取群荣誉信息
+    pub fn get_group_honor_info_typed(
+        &self,
+        group_id: i64,
+        honor_type: HonorType,
+    ) -> impl std::future::Future<Output = Result<HonorInfo, ApiError>> {
+        let response = self.get_group_honor_info(group_id, honor_type);
+        async move { parse_data(response.await?) }
+    }
+
+    /// 获取相关接口凭证
+    pub fn get_credentials_typed(
+        &self,
+        domain: &str,
+    ) -> impl std::future::Future<Output = Result<Credentials, ApiError>> {
+        let response = self.get_credentials(domain);
+        async move { parse_data(response.await?) }
+    }
+
+    /// 获取运行状态
+    pub fn get_status_typed(&self) -> impl std::future::Future<Output = Result<Status, ApiError>> {
+        let response = self.get_status();
+        async move { parse_data(response.await?) }
+    }
+
+    /// 获取版本信息
+    pub fn get_version_info_typed(
+        &self,
+    ) -> impl std::future::Future<Output = Result<VersionInfo, ApiError>> {
+        let response = self.get_version_info();
+        async move { parse_data(response.await?) }
+    }
+
+    /// 获取 Cookies
+    pub fn get_cookies_typed(
+        &self,
+        domain: &str,
+    ) -> impl std::future::Future<Output = Result<Cookies, ApiError>> {
+        let response = self.get_cookies(domain);
+        async move { parse_data(response.await?) }
+    }
+
+    /// 获取 CSRF Token
+    pub fn get_csrf_token_typed(
+        &self,
+    ) -> impl std::future::Future<Output = Result<CsrfToken, ApiError>> {
+        let response = self.get_csrf_token();
+        async move { parse_data(response.await?) }
+    }
+
+    /// 获取语音
+    pub fn get_record_typed(
+        &self,
+        file: &str,
+        out_format: &str,
+    ) -> impl std::future::Future<Output = Result<FileInfo, ApiError>> {
+        let response = self.get_record(file, out_format);
+        async move { parse_data(response.await?) }
+    }
+
+    /// 获取图片
+    pub fn get_image_typed(
+        &self,
+        file: &str,
+    ) -> impl std::future::Future<Output = Result<FileInfo, ApiError>> {
+        let response = self.get_image(file);
+        async move { parse_data(response.await?) }
     }
 }
 
@@ -994,12 +1160,47 @@ impl RuntimeBot {
     /// `action`: 拓展 Api 的方法名
     ///
     /// `params`: 参数
+    ///
+    /// 超过配置的 `api_timeout` 未返回时，返回 `ApiError::Timeout`。失败时按照重试策略重试
     pub fn send_api_return(
         &self,
         action: &str,
         params: Value,
-    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiReturn>> {
+    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
         let send_api = SendApi::new(action, params, &rand_echo());
-        send_api_request_with_response(&self.api_tx, send_api)
+        self.send_api_with_response(send_api)
+    }
+    /// 发送拓展 Api, 此方法关注返回值，并使用指定的超时时间代替配置的 `api_timeout`。
+    ///
+    /// # Arguments
+    ///
+    /// `action`: 拓展 Api 的方法名
+    ///
+    /// `params`: 参数
+    ///
+    /// `timeout`: 等待返回值的最长时间，重试时每次都使用此时间
+    pub fn send_api_return_with_timeout(
+        &self,
+        action: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> impl std::future::Future<Output = Result<ApiReturn, ApiError>> {
+        let send_api = SendApi::new(action, params, &rand_echo());
+        self.send_api_with_response_timeout(send_api, Some(timeout))
+    }
+    /// 发送拓展 Api, 并将返回值的 `data` 解析为 `T`。
+    ///
+    /// # Arguments
+    ///
+    /// `action`: 拓展 Api 的方法名
+    ///
+    /// `params`: 参数
+    pub fn send_api_return_typed<T: DeserializeOwned>(
+        &self,
+        action: &str,
+        params: Value,
+    ) -> impl std::future::Future<Output = Result<T, ApiError>> {
+        let response = self.send_api_return(action, params);
+        async move { parse_data(response.await?) }
     }
 }
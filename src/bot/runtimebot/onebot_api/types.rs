@@ -0,0 +1,275 @@
+//! OneBot v11 api 返回值 `data` 对应的结构体。
+//!
+//! 缺少的可选字段使用默认值，缺少 id 等必需字段时解析失败，返回 `ApiError::ParseError`。
+//! 各实现拓展的字段可以通过原始的 `ApiReturn` 获取。
+
+use crate::bot::plugin_builder::event::{lenient, Sex};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+
+/// `get_login_info` 的返回值
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct LoginInfo {
+    pub user_id: i64,
+    #[serde(default)]
+    pub nickname: String,
+}
+
+/// `get_stranger_info` 的返回值
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct StrangerInfo {
+    pub user_id: i64,
+    #[serde(default)]
+    pub nickname: String,
+    /// 性别，`unknown` 时为 `None`
+    #[serde(default, deserialize_with = "lenient")]
+    pub sex: Option<Sex>,
+    #[serde(default)]
+    pub age: i32,
+}
+
+/// `get_friend_list` 返回的好友
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct FriendInfo {
+    pub user_id: i64,
+    #[serde(default)]
+    pub nickname: String,
+    /// 备注名
+    #[serde(default)]
+    pub remark: String,
+}
+
+/// `get_group_info` 与 `get_group_list` 返回的群
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct GroupInfo {
+    pub group_id: i64,
+    #[serde(default)]
+    pub group_name: String,
+    /// 成员数
+    #[serde(default)]
+    pub member_count: i32,
+    /// 最大成员数（群容量）
+    #[serde(default)]
+    pub max_member_count: i32,
+}
+
+/// `get_group_member_info` 与 `get_group_member_list` 返回的群成员
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct GroupMemberInfo {
+    pub group_id: i64,
+    pub user_id: i64,
+    #[serde(default)]
+    pub nickname: String,
+    /// 群名片／备注
+    #[serde(default)]
+    pub card: String,
+    /// 性别，`unknown` 时为 `None`
+    #[serde(default, deserialize_with = "lenient")]
+    pub sex: Option<Sex>,
+    #[serde(default)]
+    pub age: i32,
+    /// 地区
+    #[serde(default)]
+    pub area: String,
+    /// 加群时间戳
+    #[serde(default)]
+    pub join_time: i64,
+    /// 最后发言时间戳
+    #[serde(default)]
+    pub last_sent_time: i64,
+    /// 成员等级，部分实现返回数字，统一转换为字符串
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub level: String,
+    /// `owner`、`admin` 或 `member`
+    #[serde(default)]
+    pub role: String,
+    /// 是否不良记录成员
+    #[serde(default)]
+    pub unfriendly: bool,
+    /// 专属头衔
+    #[serde(default)]
+    pub title: String,
+    /// 专属头衔过期时间戳
+    #[serde(default)]
+    pub title_expire_time: i64,
+    /// 是否允许修改群名片
+    #[serde(default)]
+    pub card_changeable: bool,
+}
+
+/// `get_group_honor_info` 的返回值，没有请求的荣誉类型为空
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct HonorInfo {
+    pub group_id: i64,
+    /// 当前龙王
+    #[serde(default)]
+    pub current_talkative: Option<CurrentTalkative>,
+    /// 历史龙王
+    #[serde(default)]
+    pub talkative_list: Vec<HonorMember>,
+    /// 群聊之火
+    #[serde(default)]
+    pub performer_list: Vec<HonorMember>,
+    /// 群聊炽焰
+    #[serde(default)]
+    pub legend_list: Vec<HonorMember>,
+    /// 冒尖小春笋
+    #[serde(default)]
+    pub strong_newbie_list: Vec<HonorMember>,
+    /// 快乐之源
+    #[serde(default)]
+    pub emotion_list: Vec<HonorMember>,
+}
+
+/// 当前龙王
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct CurrentTalkative {
+    pub user_id: i64,
+    #[serde(default)]
+    pub nickname: String,
+    /// 头像 URL
+    #[serde(default)]
+    pub avatar: String,
+    /// 持续天数
+    #[serde(default)]
+    pub day_count: i32,
+}
+
+/// 获得群荣誉的成员
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct HonorMember {
+    pub user_id: i64,
+    #[serde(default)]
+    pub nickname: String,
+    /// 头像 URL
+    #[serde(default)]
+    pub avatar: String,
+    /// 荣誉描述
+    #[serde(default)]
+    pub description: String,
+}
+
+/// `get_cookies` 的返回值
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Cookies {
+    #[serde(default)]
+    pub cookies: String,
+}
+
+/// `get_csrf_token` 的返回值
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct CsrfToken {
+    #[serde(default)]
+    pub token: i64,
+}
+
+/// `get_credentials` 的返回值
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Credentials {
+    #[serde(default)]
+    pub cookies: String,
+    #[serde(default)]
+    pub csrf_token: i64,
+}
+
+/// `get_record` 与 `get_image` 的返回值
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct FileInfo {
+    /// 转换后的文件路径
+    #[serde(default)]
+    pub file: String,
+}
+
+/// `get_status` 的返回值
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Status {
+    /// 当前 QQ 在线，`None` 表示无法查询到在线状态
+    #[serde(default)]
+    pub online: Option<bool>,
+    /// 状态符合预期，意味着各模块正常运行、功能正常，且 QQ 在线
+    #[serde(default)]
+    pub good: bool,
+    /// 实现拓展的字段
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// `get_version_info` 的返回值
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct VersionInfo {
+    /// 应用标识，如 `mirai-native`
+    #[serde(default)]
+    pub app_name: String,
+    /// 应用版本，如 `1.2.3`
+    #[serde(default)]
+    pub app_version: String,
+    /// OneBot 标准版本，如 `v11`
+    #[serde(default)]
+    pub protocol_version: String,
+    /// 实现拓展的字段
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// `send_group_forward_msg` 与 `send_private_forward_msg` 的返回值
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ForwardMsgReturn {
+    pub message_id: i32,
+    /// 合并转发 ID，可以用于 `get_forward_msg`
+    #[serde(default, alias = "forward_id", deserialize_with = "string_or_number")]
+    pub res_id: String,
+}
+
+fn string_or_number<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(s) => s,
+        Value::Null => String::new(),
+        v => v.to_string(),
+    })
+}
+
+#[test]
+fn parse_group_member_info() {
+    let data = serde_json::json!({
+        "group_id": 1,
+        "user_id": 2,
+        "nickname": "a",
+        "level": 3,
+        "role": "admin",
+        "is_robot": false,
+    });
+    let info: GroupMemberInfo = serde_json::from_value(data).unwrap();
+    assert_eq!(info.level, "3");
+    assert_eq!(info.role, "admin");
+    assert_eq!(info.card, "");
+    assert_eq!(info.sex, None);
+
+    let data = serde_json::json!({ "user_id": 2, "nickname": "a", "sex": "female" });
+    let info: StrangerInfo = serde_json::from_value(data).unwrap();
+    assert_eq!(info.sex, Some(Sex::Female));
+
+    // 缺少必需的 id 时解析失败
+    let raw: crate::bot::ApiReturn = serde_json::from_value(serde_json::json!({
+        "status": "ok",
+        "retcode": 0,
+        "data": { "user_id": 2, "nickname": "a" },
+        "echo": "",
+    }))
+    .unwrap();
+    assert!(matches!(
+        super::parse_data::<GroupMemberInfo>(raw),
+        Err(crate::error::ApiError::ParseError { .. })
+    ));
+
+    let data = serde_json::json!({
+        "app_name": "napcat",
+        "app_version": "1.0",
+        "protocol_version": "v11",
+        "nt_protocol": "linux",
+    });
+    let info: VersionInfo = serde_json::from_value(data).unwrap();
+    assert_eq!(info.extra["nt_protocol"], "linux");
+}
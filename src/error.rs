@@ -1,3 +1,4 @@
+use crate::bot::ApiReturn;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -5,6 +6,12 @@ pub enum MessageError {
     /// 解析出错
     #[error("Parse error: {0}")]
     ParseError(String),
+    /// 无法读取媒体文件
+    #[error("Failed to read media: {0}")]
+    MediaReadError(String),
+    /// 媒体超过大小限制，单位字节
+    #[error("Media is too large: {size} bytes, limit is {limit} bytes")]
+    MediaTooLarge { size: u64, limit: u64 },
     // #[error("Error, and no one knows why something went wrong")]
     // UnknownError(),
 }
@@ -14,10 +21,96 @@ pub enum BotError {
     /// 没有寻找到插件
     #[error("Plugin not found: {0}")]
     PluginNotFound(String),
+    /// 没有寻找到账号
+    #[error("Account not found: {0}")]
+    AccountNotFound(i64),
+    /// 账号还没有登录，或者登录信息还没有获取到
+    #[error("Bot is not logged in yet")]
+    NotLoggedIn,
     #[error("Bot's Weak reference has expired")]
     RefExpired,
 }
 
+#[derive(Error, Debug)]
+pub enum ApiError {
+    /// OneBot 服务端返回了失败，`raw` 为原始返回值
+    #[error("Api failed, retcode: {retcode}, message: {message}")]
+    Failed {
+        retcode: i32,
+        message: String,
+        wording: String,
+        raw: Box<ApiReturn>,
+    },
+    /// 等待返回值超时
+    #[error("Api timed out")]
+    Timeout,
+    /// api 已发送，但连接在收到返回值前断开
+    #[error("Connection closed before the api returned")]
+    ConnectionClosed,
+    /// 返回值 `data` 的结构与预期不同，`raw` 为原始返回值
+    #[error("Failed to parse api return: {message}")]
+    ParseError {
+        message: String,
+        raw: Box<ApiReturn>,
+    },
+    /// api 没有被发送，通常是 Bot 正在关闭
+    #[error("Api cancelled")]
+    Cancelled,
+    /// api 队列已满，api 按照配置的 `OverflowPolicy` 被丢弃或拒绝
+    #[error("Api queue is full")]
+    QueueFull,
+}
+
+impl ApiError {
+    /// 获取原始返回值，只有 `Failed` 与 `ParseError` 有原始返回值
+    pub fn raw(&self) -> Option<&ApiReturn> {
+        match self {
+            ApiError::Failed { raw, .. } | ApiError::ParseError { raw, .. } => Some(raw),
+            _ => None,
+        }
+    }
+}
+
+impl From<ApiReturn> for ApiError {
+    fn from(raw: ApiReturn) -> Self {
+        ApiError::Failed {
+            retcode: raw.retcode,
+            message: raw.message.clone(),
+            wording: raw.wording.clone(),
+            raw: Box::new(raw),
+        }
+    }
+}
+
+/// 放入队列失败，返回没有放入的元素
+#[derive(Error)]
+pub enum QueueError<T> {
+    /// 队列已满
+    #[error("Queue is full")]
+    Full(T),
+    /// 接收端已关闭
+    #[error("Queue is closed")]
+    Closed(T),
+}
+
+impl<T> QueueError<T> {
+    /// 取回没有放入的元素
+    pub fn into_inner(self) -> T {
+        match self {
+            QueueError::Full(v) | QueueError::Closed(v) => v,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for QueueError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueueError::Full(_) => f.write_str("Full(..)"),
+            QueueError::Closed(_) => f.write_str("Closed(..)"),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum BotBuildError {
     /// 解析TOML文件失败
@@ -22,10 +22,15 @@ pub use bot::plugin_builder::PluginBuilder;
 pub use bot::runtimebot::RuntimeBot;
 pub use bot::ApiReturn;
 pub use bot::Bot;
+pub use error::ApiError;
 pub use error::MessageError;
 pub use kovi_macros::plugin;
 pub use task::spawn;
 
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub use bot::handler::DispatchBench;
+
 #[deprecated(since = "0.11.0", note = "请使用 `MsgEvent` 代替")]
 pub type AllMsgEvent = bot::plugin_builder::event::MsgEvent;
 #[deprecated(since = "0.11.0", note = "请使用 `NoticeEvent` 代替")]
@@ -41,5 +46,5 @@ pub use serde_json;
 pub use tokio;
 pub use toml;
 
-#[cfg(feature = "cqstring")]
+/// 兼容, 到0.12删除。CQ 码已不再使用正则解析，需要时请直接依赖 `regex`
 pub use regex;